@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{models, Cnf, Lit, Var};
+
+/// The result of `approx_count`.
+///
+/// With probability at least `1 - delta`, `estimate` is within a factor `1 + epsilon` of the
+/// number of models projected on the sampling set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApproxCount {
+    pub estimate: BigUint,
+    pub epsilon: f64,
+    pub delta: f64,
+    // true when the formula has so few models that they were counted exactly
+    pub exact: bool,
+    // the cells that are counted at most, and the count of hashes the median is taken over
+    pub threshold: usize,
+    pub iterations: usize,
+}
+
+// a hash that leaves too many models in its smallest cell is replaced by a new one, at most this
+// many times the count of iterations hashes are tried
+const MAX_HASHES: usize = 4;
+
+/// Approximates the number of models of cnf, projected on the sampling set, in the way of
+/// ApproxMC.
+///
+/// Random xor constraints over the sampling set split the models into cells of about the same
+/// size. For each of several random hashes, the smallest number of xors that leaves fewer than
+/// `threshold` models in the cell is searched (the xors of a hash are used as prefixes, so the
+/// count only decreases), and the models of the cell times the number of cells is an estimate.
+/// The median of the estimates is returned.
+///
+/// Without a sampling set all the vars of cnf are used. All random choices come from `seed`.
+///
+/// A hash whose xors all together still leave `threshold` models gives no estimate and a new hash
+/// is drawn. Returns None when too many hashes fail to collect the estimates.
+pub fn approx_count(
+    cnf: &Cnf,
+    sampling: Option<&[Var]>,
+    epsilon: f64,
+    delta: f64,
+    seed: u64,
+) -> Option<ApproxCount> {
+    assert!(epsilon > 0.0 && delta > 0.0 && delta < 1.0);
+    let vars = match sampling {
+        Some(vars) => vars.to_vec(),
+        None => (0..cnf.n_lit).map(Var::from_index).collect(),
+    };
+    let threshold = (1.0 + 9.84 * (1.0 + epsilon / (1.0 + epsilon)) * (1.0 + 1.0 / epsilon).powi(2))
+        .ceil() as usize;
+    let iterations = (17.0 * (3.0 / delta).log2()).ceil() as usize;
+    let mut res = ApproxCount {
+        estimate: BigUint::from(0u32),
+        epsilon,
+        delta,
+        exact: true,
+        threshold,
+        iterations: 0,
+    };
+
+    // few enough models to count them all
+    let all = bounded_count(cnf, &vars, &[], threshold);
+    if all < threshold {
+        res.estimate = BigUint::from(all);
+        return Some(res);
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut estimates = vec![];
+    for _ in 0..MAX_HASHES * iterations {
+        if estimates.len() == iterations {
+            break;
+        }
+        let xors = (0..vars.len())
+            .map(|_| {
+                let lits = vars
+                    .iter()
+                    .filter(|_| rng.gen())
+                    .map(|var| var.positive())
+                    .collect::<Vec<_>>();
+                (lits, rng.gen::<bool>())
+            })
+            .collect::<Vec<_>>();
+
+        estimates.extend(estimate(cnf, &vars, all, &xors, threshold));
+    }
+    if estimates.len() < iterations {
+        return None;
+    }
+
+    estimates.sort();
+    res.exact = false;
+    res.iterations = estimates.len();
+    res.estimate = estimates.swap_remove(estimates.len() / 2);
+    Some(res)
+}
+
+// the models of the smallest cell of the xors prefixes with fewer than threshold models, times
+// the number of cells
+// return None: all the xors leave too many models
+fn estimate(
+    cnf: &Cnf,
+    vars: &[Var],
+    all: usize,
+    xors: &[(Vec<Lit>, bool)],
+    threshold: usize,
+) -> Option<BigUint> {
+    // binary search for the smallest m where the cell of the first m xors is small
+    let mut counts = HashMap::from([(0, all)]);
+    let mut count = |m: usize| {
+        *counts
+            .entry(m)
+            .or_insert_with(|| bounded_count(cnf, vars, &xors[..m], threshold))
+    };
+    if count(xors.len()) >= threshold {
+        return None;
+    }
+    let (mut lo, mut hi) = (0, xors.len());
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if count(mid) >= threshold {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Some(BigUint::from(count(hi)) << hi)
+}
+
+// the count of the models of cnf with the xors, projected on vars, up to threshold
+fn bounded_count(cnf: &Cnf, vars: &[Var], xors: &[(Vec<Lit>, bool)], threshold: usize) -> usize {
+    let mut cnf = cnf.clone();
+    for (lits, rhs) in xors {
+        cnf.add_xor(lits, *rhs);
+    }
+    let mut count = 0;
+    for cube in models(&cnf, Some(vars)) {
+        // the vars of the projection that the cube does not assign are don't-cares
+        let free = vars.len() - cube.assigned().len();
+        if free >= usize::BITS as usize - 1 {
+            return threshold;
+        }
+        count += 1 << free;
+        if count >= threshold {
+            return threshold;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+
+    use num_bigint::BigUint;
+
+    use super::estimate;
+    use crate::*;
+
+    #[test]
+    fn approx_exact() {
+        let clauses = vec![vec![1, 2], vec![-3]];
+        let cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+        let res = approx_count(&cnf, None, 0.8, 0.2, 31).unwrap();
+        assert!(res.exact);
+        assert_eq!(res.estimate, BigUint::from(3u32));
+    }
+
+    #[test]
+    fn approx_estimate() {
+        // (x1 || x2) && (x3 || x4) over 20 vars: 9 * 2^16 models
+        let mut clauses = vec![vec![1, 2], vec![3, 4]];
+        clauses.push(vec![20, -20]);
+        let cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+        let res = approx_count(&cnf, None, 0.8, 0.2, 31).unwrap();
+        assert!(!res.exact);
+        assert_eq!(res.iterations, 67);
+        let expected = 9.0 * 2f64.powi(16);
+        let estimate = res.estimate.to_string().parse::<f64>().unwrap();
+        assert!(
+            expected / 1.8 <= estimate && estimate <= expected * 1.8,
+            "{}",
+            estimate
+        );
+    }
+
+    #[test]
+    fn approx_projected() {
+        let clauses = vec![vec![1, 2], vec![3, 4], vec![-1, 5, 6, 7]];
+        let cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+        let vars = [1, 2, 3, 4].map(Var::from_dimacs);
+        let res = approx_count(&cnf, Some(&vars), 0.8, 0.2, 31).unwrap();
+        assert!(res.exact);
+        assert_eq!(res.estimate, BigUint::from(9u32));
+    }
+
+    #[test]
+    fn approx_hash_fails() {
+        // xors without lits that are always true do not split the 64 models of 6 free vars
+        let cnf = Cnf::from(Clauses::from(&[vec![6, -6]][..]));
+        let vars = (1..=6).map(Var::from_dimacs).collect::<Vec<_>>();
+        let trivial = vec![(vec![], false); 6];
+        assert_eq!(estimate(&cnf, &vars, 64, &trivial, 20), None);
+        let split = vars
+            .iter()
+            .map(|var| (vec![var.positive()], true))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            estimate(&cnf, &vars, 64, &split, 20),
+            Some(BigUint::from(64u32))
+        );
+    }
+}
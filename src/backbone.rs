@@ -0,0 +1,106 @@
+use crate::{dpll_assuming, Clause, Cnf, Lit};
+
+/// The backbone of cnf: the lits that are true in every model, sorted.
+///
+/// Returns None when cnf has no model.
+///
+/// The candidates are the lits of a first model. Every further model removes the candidates it
+/// does not agree with, a var that a model leaves unassigned is free and is never in the backbone.
+/// The candidates are tested `chunk` at a time: when no model falsifies any lit of the chunk, the
+/// whole chunk is in the backbone and is added as units for the next calls. With a chunk of 1 each
+/// candidate is tested alone by assuming its negation.
+pub fn backbone(cnf: &Cnf, chunk: usize) -> Option<Vec<Lit>> {
+    let model = dpll_assuming(cnf, &[]).ok()?;
+    let mut candidates = model.assigned();
+    let mut cnf = cnf.clone();
+    let mut res = vec![];
+    while !candidates.is_empty() {
+        let rest = candidates.len() - chunk.clamp(1, candidates.len());
+        let tested = candidates[rest..].to_vec();
+        let model = if tested.len() == 1 {
+            dpll_assuming(&cnf, &[!tested[0]])
+        } else {
+            // a model that falsifies at least one of the tested lits
+            let mut cnf = cnf.clone();
+            cnf.add_clause(Clause(tested.iter().map(|&lit| !lit).collect()));
+            dpll_assuming(&cnf, &[])
+        };
+        match model {
+            Ok(model) => {
+                candidates.retain(|lit| model.value(lit.var()) == Some(lit.is_positive()));
+            }
+            Err(_) => {
+                candidates.truncate(rest);
+                for lit in tested {
+                    cnf.add_clause(Clause(vec![lit]));
+                    res.push(lit);
+                }
+            }
+        }
+    }
+    res.sort();
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{random_lit, satisfies};
+    use crate::*;
+
+    #[test]
+    fn backbone_small() {
+        // 1 is forced, 2 and 3 are not, 4 is forced by 1
+        let clauses = vec![vec![1], vec![2, 3], vec![-1, -4]];
+        let cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+        let expected = vec![Lit::from_dimacs(1), Lit::from_dimacs(-4)];
+        assert_eq!(backbone(&cnf, 1), Some(expected.clone()));
+        assert_eq!(backbone(&cnf, 8), Some(expected));
+
+        let clauses = vec![vec![1], vec![-1]];
+        let cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+        assert_eq!(backbone(&cnf, 1), None);
+    }
+
+    #[test]
+    fn backbone_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(32);
+        let n = 6;
+        for _ in 0..300 {
+            let clauses = (0..rng.gen_range(1..14))
+                .map(|_| {
+                    (0..rng.gen_range(1..4))
+                        .map(|_| random_lit(&mut rng, n))
+                        .collect()
+                })
+                .collect::<Vec<Vec<i32>>>();
+            let models = (0..1u32 << n)
+                .filter(|&bits| satisfies(&clauses, bits))
+                .collect::<Vec<_>>();
+            let expected = if models.is_empty() {
+                None
+            } else {
+                let mut lits = vec![];
+                for var in 1..=n {
+                    let values = models
+                        .iter()
+                        .map(|bits| bits >> (var - 1) & 1 == 1)
+                        .collect::<std::collections::HashSet<_>>();
+                    if values.len() == 1 {
+                        let sign = if values.contains(&true) { 1 } else { -1 };
+                        lits.push(Lit::from_dimacs((var * sign) as isize));
+                    }
+                }
+                lits.sort();
+                Some(lits)
+            };
+
+            let cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+            for chunk in [1, 3, 16] {
+                assert_eq!(backbone(&cnf, chunk), expected, "{:?}", clauses);
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+// simplify a DIMACS CNF for another solver, and map its model back to the original formula
+//
+// dpll-rs-preprocess [--passes p1,p2,..] [--freeze v1,v2,..] <input> <output> <reconstruction>
+// dpll-rs-preprocess reconstruct <reconstruction> <model>
+//
+// the passes run in the given order, they are subsume, eliminate, probe, equivalences, blocked,
+// covered and vivify; the reconstructed model is written to stdout
+
+use std::{env, fs, process};
+
+use dpll_rs::{parse_dimacs, parse_model, write_model, Dimacs, Preprocessor, Reconstruction, Var};
+
+const USAGE: &str = "usage:
+  dpll-rs-preprocess [--passes p1,p2,..] [--freeze v1,v2,..] <input> <output> <reconstruction>
+  dpll-rs-preprocess reconstruct <reconstruction> <model>
+
+passes: subsume, eliminate, probe, equivalences, blocked, covered, vivify
+        (default: subsume,probe,equivalences,eliminate)";
+
+const DEFAULT_PASSES: &str = "subsume,probe,equivalences,eliminate";
+// the propagations that vivification may spend
+const VIVIFY_BUDGET: usize = 1_000_000;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let res = match args.first().map(String::as_str) {
+        Some("reconstruct") => reconstruct(&args[1..]),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => preprocess(&args),
+    };
+    if let Err(message) = res {
+        eprintln!("dpll-rs-preprocess: {}", message);
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+}
+
+fn preprocess(args: &[String]) -> Result<(), String> {
+    let mut passes = DEFAULT_PASSES.to_string();
+    let mut frozen = vec![];
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--passes" => passes = args.next().ok_or("missing passes")?.clone(),
+            "--freeze" => {
+                for var in args.next().ok_or("missing vars")?.split(',') {
+                    match var.parse::<isize>() {
+                        Ok(var) if var > 0 => frozen.push(Var::from_dimacs(var)),
+                        _ => return Err(format!("invalid var `{}`", var)),
+                    }
+                }
+            }
+            _ => files.push(arg.as_str()),
+        }
+    }
+    let [input, output, reconstruction] = files[..] else {
+        return Err("expected an input, an output and a reconstruction file".to_string());
+    };
+
+    let dimacs = read(input)?;
+    let dimacs = parse_dimacs(&dimacs).map_err(|err| format!("{}: {}", input, err))?;
+    let mut preprocessor = Preprocessor::new(&dimacs.clauses);
+    // the xor constraints are passed through, so their vars have to stay
+    for var in frozen.into_iter().chain(
+        dimacs
+            .xors
+            .iter()
+            .flat_map(|(lits, _)| lits.iter().map(|lit| lit.var())),
+    ) {
+        preprocessor.freeze(var);
+    }
+
+    let mut stats = vec![];
+    for pass in passes.split(',') {
+        let stat = match pass {
+            "subsume" => {
+                let res = preprocessor.subsume();
+                format!("{} removed, {} strengthened", res.removed, res.strengthened)
+            }
+            "eliminate" => format!("{} vars eliminated", preprocessor.eliminate()),
+            "probe" => format!("{} units", preprocessor.probe()),
+            "equivalences" => format!("{} vars replaced", preprocessor.substitute_equivalences()),
+            "blocked" => format!("{} removed", preprocessor.eliminate_blocked()),
+            "covered" => format!("{} removed", preprocessor.eliminate_covered()),
+            "vivify" => {
+                let res = preprocessor.vivify(VIVIFY_BUDGET);
+                format!("{} removed, {} shortened", res.removed, res.shortened)
+            }
+            _ => return Err(format!("unknown pass `{}`", pass)),
+        };
+        stats.push(format!("c {}: {}\n", pass, stat));
+    }
+
+    let simplified = Dimacs {
+        n_var: dimacs.n_var,
+        clauses: preprocessor.clauses(),
+        xors: dimacs.xors,
+    };
+    write(output, &(stats.concat() + &simplified.to_string()))?;
+    write(reconstruction, &preprocessor.reconstruction().to_string())?;
+    print!("{}", stats.concat());
+    Ok(())
+}
+
+fn reconstruct(args: &[String]) -> Result<(), String> {
+    let [reconstruction, model] = args else {
+        return Err("expected a reconstruction and a model file".to_string());
+    };
+    let reconstruction = read(reconstruction)?
+        .parse::<Reconstruction>()
+        .map_err(|err| format!("{}: {}", reconstruction, err))?;
+    let output = read(model)?;
+    if output.lines().any(|line| line.trim() == "s UNSATISFIABLE") {
+        println!("s UNSATISFIABLE");
+        return Ok(());
+    }
+    let model = parse_model(&output).map_err(|err| format!("{}: {}", model, err))?;
+    println!("s SATISFIABLE");
+    print!("{}", write_model(&reconstruction.extend(&model)));
+    Ok(())
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn write(path: &str, contents: &str) -> Result<(), String> {
+    fs::write(path, contents).map_err(|err| format!("{}: {}", path, err))
+}
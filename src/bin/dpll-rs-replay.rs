@@ -0,0 +1,59 @@
+// search a DIMACS CNF again as a trace of `dpll-rs --trace` recorded it, and report the first
+// event where the search leaves the trace
+//
+// dpll-rs-replay <input> <trace>
+//
+// the exit code is 0 when the search follows the whole trace and 2 when it diverges
+
+use std::{env, fs, process};
+
+use dpll_rs::{parse_dimacs, replay, Engine, Trace};
+
+const USAGE: &str = "usage:
+  dpll-rs-replay <input> <trace>";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if matches!(
+        args.first().map(String::as_str),
+        Some("-h") | Some("--help")
+    ) {
+        println!("{}", USAGE);
+        return;
+    }
+    match check(&args) {
+        Ok(code) => process::exit(code),
+        Err(message) => {
+            eprintln!("dpll-rs-replay: {}", message);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    }
+}
+
+fn check(args: &[String]) -> Result<i32, String> {
+    let [input, trace] = args else {
+        return Err("expected an input and a trace file".to_string());
+    };
+    let dimacs = fs::read_to_string(input).map_err(|err| format!("{}: {}", input, err))?;
+    let dimacs = parse_dimacs(&dimacs).map_err(|err| format!("{}: {}", input, err))?;
+    let file = fs::File::open(trace).map_err(|err| format!("{}: {}", trace, err))?;
+    let trace = Trace::read(file).map_err(|err| format!("{}: {}", trace, err))?;
+
+    let engine = match trace.engine {
+        Engine::Dpll => "dpll".to_string(),
+        Engine::Cfcl(strategy) => format!("cfcl, {:?} guesses", strategy),
+    };
+    println!("c engine: {}, seed: {}", engine, trace.seed);
+    println!("c events: {}", trace.events.len());
+    Ok(match replay(&dimacs, &trace) {
+        Ok(events) => {
+            println!("c replayed {} events", events);
+            0
+        }
+        Err(divergence) => {
+            println!("c diverged at {}", divergence);
+            2
+        }
+    })
+}
@@ -0,0 +1,205 @@
+// solve a DIMACS CNF, the result is printed as in the SAT competition
+//
+// dpll-rs [--engine cfcl|dpll] [--seed n] [--conflicts n] [--decisions n] [--propagations n]
+//         [--time seconds] [--memory bytes] [--progress conflicts] [--stats-json file]
+//         [--dot file] [--dot-conflict n] [--trace file] <input>
+//
+// the statistics are printed as `c` lines, and written as JSON with --stats-json; --progress
+// prints a row of statistics every given count of conflicts
+// --dot writes the implication graph of cfcl at its conflict number --dot-conflict, the first
+// one by default, in Graphviz DOT
+// --trace writes every event of the search in a binary trace, that dpll-rs-replay checks
+// Ctrl-C stops the search, then the statistics and `s UNKNOWN` are printed
+
+use std::{
+    env,
+    fs::{self, File},
+    io::BufWriter,
+    process,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use dpll_rs::{
+    cfcl_limited, cfcl_observed, dpll_limited, dpll_observed, parse_dimacs, write_model, Budget,
+    Cnf, CnfGraph, Engine, SolveResult, Stats, Strategy, Terminate, TraceWriter,
+};
+
+const USAGE: &str = "usage:
+  dpll-rs [--engine cfcl|dpll] [--seed n] [--conflicts n] [--decisions n] [--propagations n]
+          [--time seconds] [--memory bytes] [--progress conflicts] [--stats-json file]
+          [--dot file] [--dot-conflict n] [--trace file] <input>";
+
+// the progress table that the solver logs, as comment lines
+struct Progress;
+
+impl Log for Progress {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            println!("c {}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if matches!(
+        args.first().map(String::as_str),
+        Some("-h") | Some("--help")
+    ) {
+        println!("{}", USAGE);
+        return;
+    }
+    match solve(&args) {
+        Ok(code) => process::exit(code),
+        Err(message) => {
+            eprintln!("dpll-rs: {}", message);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    }
+}
+
+// return the exit code: 10 for a model, 20 when there is none, 0 when unknown
+fn solve(args: &[String]) -> Result<i32, String> {
+    let mut engine = "cfcl".to_string();
+    let mut seed = 0;
+    let mut budget = Budget::default();
+    let mut progress = None;
+    let mut json = None;
+    let mut dot = None;
+    let mut dot_conflict = 1;
+    let mut trace = None;
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value of `{}`", arg));
+        match arg.as_str() {
+            "--engine" => engine = value()?.clone(),
+            "--seed" => seed = number(value()?)?,
+            "--conflicts" => budget.conflicts = Some(number(value()?)?),
+            "--decisions" => budget.decisions = Some(number(value()?)?),
+            "--propagations" => budget.propagations = Some(number(value()?)?),
+            "--time" => budget.time = Some(Duration::from_secs(number(value()?)?)),
+            "--memory" => budget.memory = Some(number(value()?)?),
+            "--progress" => progress = Some(number(value()?)?),
+            "--stats-json" => json = Some(value()?.clone()),
+            "--dot" => dot = Some(value()?.clone()),
+            "--dot-conflict" => dot_conflict = number(value()?)?,
+            "--trace" => trace = Some(value()?.clone()),
+            _ => files.push(arg.as_str()),
+        }
+    }
+    let [input] = files[..] else {
+        return Err("expected an input file".to_string());
+    };
+
+    let dimacs = fs::read_to_string(input).map_err(|err| format!("{}: {}", input, err))?;
+    let dimacs = parse_dimacs(&dimacs).map_err(|err| format!("{}: {}", input, err))?;
+    let interrupted = Arc::new(AtomicBool::new(false));
+    budget.terminate = Some(Terminate::Flag(interrupted.clone()));
+    ctrlc::set_handler(move || {
+        interrupted.store(true, std::sync::atomic::Ordering::Relaxed);
+    })
+    .map_err(|err| err.to_string())?;
+
+    if progress.is_some() {
+        log::set_logger(&Progress).map_err(|err| err.to_string())?;
+        log::set_max_level(LevelFilter::Info);
+    }
+
+    let engine = match engine.as_str() {
+        "cfcl" => Engine::Cfcl(Strategy::Direct),
+        "dpll" if dot.is_some() => {
+            return Err("--dot needs the implication graph of the cfcl engine".to_string())
+        }
+        "dpll" => Engine::Dpll,
+        _ => return Err(format!("unknown engine `{}`", engine)),
+    };
+    let mut trace = match trace {
+        Some(path) => {
+            let file = File::create(&path).map_err(|err| format!("{}: {}", path, err))?;
+            let writer = TraceWriter::new(BufWriter::new(file), engine, seed)
+                .map_err(|err| format!("{}: {}", path, err))?;
+            Some((path, writer))
+        }
+        None => None,
+    };
+
+    let (res, stats): (SolveResult, Stats) = match engine {
+        Engine::Cfcl(strategy) => {
+            let mut cnf = CnfGraph::from(dimacs);
+            cnf.set_seed(seed);
+            cnf.strategy = strategy;
+            cnf.progress = progress;
+            cnf.dot_conflict = dot.as_ref().map(|_| dot_conflict);
+            let res = match &mut trace {
+                Some((_, writer)) => cfcl_observed(&mut cnf, &budget, writer),
+                None => cfcl_limited(&mut cnf, &budget),
+            };
+            if let Some(path) = &dot {
+                match &cnf.dot {
+                    Some(graph) => {
+                        fs::write(path, graph).map_err(|err| format!("{}: {}", path, err))?
+                    }
+                    None => println!(
+                        "c no conflict number {}, {} not written",
+                        dot_conflict, path
+                    ),
+                }
+            }
+            (res, cnf.stats)
+        }
+        Engine::Dpll => {
+            let mut cnf = Cnf::from(dimacs);
+            cnf.set_seed(seed);
+            cnf.progress = progress;
+            let res = match &mut trace {
+                Some((_, writer)) => dpll_observed(&mut cnf, &budget, writer),
+                None => dpll_limited(&mut cnf, &budget),
+            };
+            (res, cnf.stats)
+        }
+    };
+    if let Some((path, writer)) = trace {
+        writer
+            .finish(&res)
+            .map_err(|err| format!("{}: {}", path, err))?;
+    }
+    for line in stats.to_string().lines() {
+        println!("c {}", line);
+    }
+    if let Some(path) = json {
+        fs::write(&path, stats.to_json() + "\n").map_err(|err| format!("{}: {}", path, err))?;
+    }
+    Ok(match res {
+        SolveResult::Sat(solution) => {
+            println!("s SATISFIABLE");
+            print!("{}", write_model(&solution));
+            10
+        }
+        SolveResult::Unsat => {
+            println!("s UNSATISFIABLE");
+            20
+        }
+        SolveResult::Unknown(reason) => {
+            println!("c stopped: {:?}", reason);
+            println!("s UNKNOWN");
+            0
+        }
+    })
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number `{}`", value))
+}
@@ -0,0 +1,224 @@
+use crate::{preprocess::resolve, Lit, Preprocessor};
+
+impl Preprocessor {
+    /// Blocked clause elimination.
+    ///
+    /// A clause is blocked on one of its lits when all its resolvents on that lit are
+    /// tautologies. Removing it keeps the formula satisfiable, and the reconstruction makes the
+    /// blocking lit true when a model falsifies the clause. Frozen vars are not used as blocking
+    /// lits.
+    ///
+    /// Returns the number of removed clauses.
+    pub fn eliminate_blocked(&mut self) -> usize {
+        let mut res = 0;
+        loop {
+            let mut changed = false;
+            for id in 0..self.clauses.len() {
+                let clause = match &self.clauses[id] {
+                    Some(clause) => clause.clone(),
+                    None => continue,
+                };
+                let blocking = clause
+                    .iter()
+                    .find(|&&lit| self.is_blocked(id, &clause, lit));
+                if let Some(&lit) = blocking {
+                    self.eliminate_clause(id, lit);
+                    res += 1;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return res;
+            }
+        }
+    }
+
+    /// Covered clause elimination.
+    ///
+    /// When every non-tautological resolvent of a clause on one of its lits shares a lit that is
+    /// not in the clause yet, that covered lit can be added to the clause. The clause is removed
+    /// when it becomes blocked this way. Every step is kept in the reconstruction:
+    /// each clause before a covered lit was added is repaired by the lit it was resolved on, and
+    /// the extended clause by its blocking lit. Frozen vars are not resolved on.
+    ///
+    /// Returns the number of removed clauses.
+    pub fn eliminate_covered(&mut self) -> usize {
+        let mut res = 0;
+        loop {
+            let mut changed = false;
+            for id in 0..self.clauses.len() {
+                if self.clauses[id].is_some() && self.eliminate_covered_clause(id) {
+                    res += 1;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return res;
+            }
+        }
+    }
+
+    // return false: the clause is not covered and is kept
+    fn eliminate_covered_clause(&mut self, id: usize) -> bool {
+        let mut clause = self.clauses[id].clone().unwrap();
+        // the clause before each covered lit was added, with the lit it was resolved on
+        let mut steps: Vec<(Lit, Vec<Lit>)> = vec![];
+        loop {
+            let mut changed = false;
+            for lit in clause.clone() {
+                if self.frozen.contains(&lit.var()) {
+                    continue;
+                }
+                let resolvents = self
+                    .occurs(!lit)
+                    .into_iter()
+                    .filter(|&other| {
+                        let other = self.clauses[other].as_ref().unwrap();
+                        resolve(&clause, other, lit.var()).is_some()
+                    })
+                    .collect::<Vec<_>>();
+                if resolvents.is_empty() {
+                    // blocked on lit
+                    self.remove_clause(id);
+                    for (witness, clause) in steps {
+                        self.reconstruction.push(witness, clause);
+                    }
+                    self.reconstruction.push(lit, clause);
+                    return true;
+                }
+
+                // the lits that every resolvent has, apart from the ones of the clause
+                let mut covered = self.clauses[resolvents[0]].clone().unwrap();
+                covered.retain(|&other| other != !lit && !clause.contains(&other));
+                for &other in resolvents[1..].iter() {
+                    let other = self.clauses[other].as_ref().unwrap();
+                    covered.retain(|lit| other.contains(lit));
+                }
+                if !covered.is_empty() {
+                    steps.push((lit, clause.clone()));
+                    clause.extend(covered);
+                    clause.sort();
+                    changed = true;
+                }
+            }
+            if !changed {
+                return false;
+            }
+        }
+    }
+
+    // every resolvent of clause id on lit is a tautology
+    fn is_blocked(&self, id: usize, clause: &[Lit], lit: Lit) -> bool {
+        !self.frozen.contains(&lit.var())
+            && self.occurs(!lit).into_iter().all(|other| {
+                other == id
+                    || resolve(clause, self.clauses[other].as_ref().unwrap(), lit.var()).is_none()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashSet;
+
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{random_lit, satisfies};
+    use crate::*;
+
+    fn to_ints(clauses: &Clauses) -> Vec<Vec<i32>> {
+        clauses
+            .0
+            .iter()
+            .map(|clause| {
+                clause
+                    .inner()
+                    .iter()
+                    .map(|lit| lit.to_dimacs() as i32)
+                    .collect()
+            })
+            .collect()
+    }
+
+    // the simplified formula is satisfiable exactly when the original one is,
+    // and its models are reconstructed to models of the original one
+    fn check(clauses: &[Vec<i32>], preprocessor: &Preprocessor, n: u32) {
+        let simplified = to_ints(&preprocessor.clauses());
+        let original = (0..1u32 << n)
+            .filter(|&bits| satisfies(clauses, bits))
+            .map(|bits| bits & 0b11)
+            .collect::<HashSet<_>>();
+        let models = (0..1u32 << n)
+            .filter(|&bits| satisfies(&simplified, bits))
+            .collect::<Vec<_>>();
+        assert_eq!(original.is_empty(), models.is_empty(), "{:?}", clauses);
+        // the frozen vars 1 and 2 keep their values
+        let projected = models
+            .iter()
+            .map(|bits| bits & 0b11)
+            .collect::<HashSet<_>>();
+        assert_eq!(original, projected, "{:?}", clauses);
+
+        for bits in models {
+            let mut model = PartialSolution::new(n as usize);
+            for i in 0..n as usize {
+                model.assign_lit(Lit::from_index(i, bits >> i & 1 == 1));
+            }
+            let model = preprocessor.reconstruction().extend(&model);
+            let bits = (0..n)
+                .filter(|&i| model.value(Var::from_index(i as usize)) == Some(true))
+                .fold(0, |bits, i| bits | 1 << i);
+            assert!(satisfies(clauses, bits), "{:?}", clauses);
+        }
+    }
+
+    #[test]
+    fn blocked_tseitin() {
+        // 4 = 1 && 2, 5 = 4 || 3, and 5 is asserted
+        let clauses = vec![
+            vec![-4, 1],
+            vec![-4, 2],
+            vec![4, -1, -2],
+            vec![-5, 4, 3],
+            vec![5, -4],
+            vec![5, -3],
+            vec![5],
+        ];
+        let formula = Clauses::from(clauses.as_slice());
+        let mut preprocessor = Preprocessor::new(&formula);
+        assert!(preprocessor.eliminate_blocked() > 0);
+        check(&clauses, &preprocessor, 5);
+
+        let mut preprocessor = Preprocessor::new(&formula);
+        assert!(preprocessor.eliminate_covered() > 0);
+        check(&clauses, &preprocessor, 5);
+    }
+
+    #[test]
+    fn blocked_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(36);
+        let n = 6;
+        for _ in 0..300 {
+            let clauses = (0..rng.gen_range(1..20))
+                .map(|_| {
+                    (0..rng.gen_range(1..4))
+                        .map(|_| random_lit(&mut rng, n))
+                        .collect()
+                })
+                .collect::<Vec<Vec<i32>>>();
+            let formula = Clauses::from(clauses.as_slice());
+            for covered in [false, true] {
+                let mut preprocessor = Preprocessor::new(&formula);
+                preprocessor.freeze(Var::from_dimacs(1));
+                preprocessor.freeze(Var::from_dimacs(2));
+                if covered {
+                    preprocessor.eliminate_covered();
+                } else {
+                    preprocessor.eliminate_blocked();
+                }
+                check(&clauses, &preprocessor, n as u32);
+            }
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use std::{
+    fmt, mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    stats::{Stats, PROGRESS_HEADER},
+    Lit, PartialSolution,
+};
+
+// the count of unit propagations between two checks of the termination
+pub(crate) const TERMINATE_INTERVAL: usize = 256;
+
+/// Stops a running search from the outside: a flag that another thread sets, or a callback that
+/// returns true once the search has to stop.
+///
+/// The search checks it at every decision and conflict, and every `TERMINATE_INTERVAL` lits
+/// during unit propagation.
+#[derive(Clone)]
+pub enum Terminate {
+    Flag(Arc<AtomicBool>),
+    Callback(Arc<dyn Fn() -> bool + Send + Sync>),
+}
+
+impl Terminate {
+    pub fn is_set(&self) -> bool {
+        match self {
+            Terminate::Flag(flag) => flag.load(Ordering::Relaxed),
+            Terminate::Callback(callback) => callback(),
+        }
+    }
+}
+
+impl fmt::Debug for Terminate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminate::Flag(flag) => f.debug_tuple("Flag").field(flag).finish(),
+            Terminate::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
+/// Limits on a search, a limit that is None is never reached.
+///
+/// The counts are checked at every decision and every conflict, so a search may go a little past
+/// a limit before it stops.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    pub conflicts: Option<usize>,
+    pub decisions: Option<usize>,
+    // lits assigned by unit propagation
+    pub propagations: Option<usize>,
+    pub time: Option<Duration>,
+    // approximate bytes of the lits of the clause database, learned clauses included, counted
+    // once for every state the search keeps to go back to
+    pub memory: Option<usize>,
+    pub terminate: Option<Terminate>,
+}
+
+/// Why a search stopped without a model or a proof that there is none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Conflicts,
+    Decisions,
+    Propagations,
+    Time,
+    Memory,
+    Interrupted,
+}
+
+/// The result of a search under a budget.
+#[derive(Debug, Clone)]
+pub enum SolveResult {
+    Sat(PartialSolution),
+    Unsat,
+    Unknown(Reason),
+}
+
+impl SolveResult {
+    pub fn is_sat(&self) -> bool {
+        matches!(self, SolveResult::Sat(_))
+    }
+
+    pub fn is_unsat(&self) -> bool {
+        matches!(self, SolveResult::Unsat)
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, SolveResult::Unknown(_))
+    }
+}
+
+// what a search has used of its budget
+#[derive(Debug, Clone)]
+pub(crate) struct Usage {
+    pub(crate) start: Instant,
+    pub(crate) stats: Stats,
+    // the memory of the clause database now
+    pub(crate) memory: usize,
+    // the count of conflicts between two rows of the progress table
+    progress: Option<usize>,
+    // the reason the search stopped, it is kept once a limit is reached
+    pub(crate) stopped: Option<Reason>,
+    // the implication graph that cfcl keeps at the conflict of `CnfGraph::dot_conflict`
+    pub(crate) dot: Option<String>,
+}
+
+impl Usage {
+    pub(crate) fn new(progress: Option<usize>) -> Usage {
+        Usage {
+            start: Instant::now(),
+            stats: Stats::default(),
+            memory: 0,
+            progress,
+            stopped: None,
+            dot: None,
+        }
+    }
+
+    pub(crate) fn set_memory(&mut self, memory: usize) {
+        self.memory = memory;
+        self.stats.memory = self.stats.memory.max(memory);
+    }
+
+    // count a conflict, and log a row of the progress table every `progress` conflicts
+    pub(crate) fn conflict(&mut self) {
+        self.stats.conflicts += 1;
+        if let Some(interval) = self.progress.filter(|&interval| interval > 0) {
+            if self.stats.conflicts == interval {
+                for line in PROGRESS_HEADER {
+                    log::info!("{}", line);
+                }
+            }
+            if self.stats.conflicts.is_multiple_of(interval) {
+                log::info!("{}", self.stats.progress(self.start.elapsed()));
+            }
+        }
+    }
+
+    // the statistics of the search once it is done
+    pub(crate) fn finish(&mut self) -> Stats {
+        self.stats.time.total = self.start.elapsed();
+        self.stats.clone()
+    }
+
+    // return true: a limit of budget is reached and the search has to stop
+    pub(crate) fn exhausted(&mut self, budget: &Budget) -> bool {
+        if self.stopped.is_some() {
+            return true;
+        }
+        let reached = |used: usize, limit: Option<usize>| limit.is_some_and(|limit| used >= limit);
+        let stats = &self.stats;
+        self.stopped = if budget
+            .terminate
+            .as_ref()
+            .is_some_and(|terminate| terminate.is_set())
+        {
+            Some(Reason::Interrupted)
+        } else if reached(stats.conflicts, budget.conflicts) {
+            Some(Reason::Conflicts)
+        } else if reached(stats.decisions, budget.decisions) {
+            Some(Reason::Decisions)
+        } else if reached(stats.propagations, budget.propagations) {
+            Some(Reason::Propagations)
+        } else if reached(self.memory, budget.memory) {
+            Some(Reason::Memory)
+        } else if budget.time.is_some_and(|time| self.start.elapsed() >= time) {
+            Some(Reason::Time)
+        } else {
+            None
+        };
+        self.stopped.is_some()
+    }
+
+    // the result of a search that returned Err, unsatisfiable unless it was stopped
+    pub(crate) fn result(&self, res: Result<PartialSolution, usize>) -> SolveResult {
+        match (res, self.stopped) {
+            (Ok(solution), _) => SolveResult::Sat(solution),
+            (Err(_), Some(reason)) => SolveResult::Unknown(reason),
+            (Err(_), None) => SolveResult::Unsat,
+        }
+    }
+}
+
+// the approximate bytes of a clause database with this count of lits, kept `states` times
+pub(crate) fn memory(lits: usize, states: usize) -> usize {
+    lits * mem::size_of::<Lit>() * states
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use rand::SeedableRng;
+
+    use crate::test_util::random_3sat;
+    use crate::*;
+
+    // the results of both engines under budget
+    fn solve_limited(clauses: &Clauses, budget: &Budget) -> [SolveResult; 2] {
+        [
+            dpll_limited(&mut Cnf::from(clauses.clone()), budget),
+            cfcl_limited(&mut CnfGraph::from(clauses.clone()), budget),
+        ]
+    }
+
+    #[test]
+    fn budget_reasons() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(45);
+        let clauses = Clauses::from(random_3sat(&mut rng, 50, 213).as_slice());
+        let budgets = [
+            (
+                Budget {
+                    conflicts: Some(5),
+                    ..Default::default()
+                },
+                Reason::Conflicts,
+            ),
+            (
+                Budget {
+                    decisions: Some(5),
+                    ..Default::default()
+                },
+                Reason::Decisions,
+            ),
+            (
+                Budget {
+                    propagations: Some(5),
+                    ..Default::default()
+                },
+                Reason::Propagations,
+            ),
+            (
+                Budget {
+                    memory: Some(1),
+                    ..Default::default()
+                },
+                Reason::Memory,
+            ),
+            (
+                Budget {
+                    time: Some(Duration::ZERO),
+                    ..Default::default()
+                },
+                Reason::Time,
+            ),
+        ];
+        for (budget, reason) in budgets {
+            for res in solve_limited(&clauses, &budget) {
+                match res {
+                    SolveResult::Unknown(stopped) => assert_eq!(stopped, reason),
+                    res => panic!("{:?} {:?}", budget, res),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn budget_unlimited() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(45);
+        for _ in 0..20 {
+            let clauses = Clauses::from(random_3sat(&mut rng, 10, 45).as_slice());
+            let expected = dpll(&mut Cnf::from(clauses.clone())).is_ok();
+            // limits that are never reached on so small formulas
+            let budget = Budget {
+                conflicts: Some(1_000_000),
+                time: Some(Duration::from_secs(3600)),
+                ..Default::default()
+            };
+            for res in solve_limited(&clauses, &budget) {
+                assert!(!res.is_unknown());
+                assert_eq!(res.is_sat(), expected);
+                assert_eq!(res.is_unsat(), !expected);
+            }
+        }
+    }
+
+    // pigeon i + 1 is in hole j + 1 when var i * holes + j + 1 is true, no two in the same hole
+    fn pigeonhole(holes: i32) -> Clauses {
+        let var = |pigeon: i32, hole: i32| pigeon * holes + hole + 1;
+        let mut clauses = (0..=holes)
+            .map(|pigeon| (0..holes).map(|hole| var(pigeon, hole)).collect())
+            .collect::<Vec<Vec<i32>>>();
+        for hole in 0..holes {
+            for first in 0..=holes {
+                for second in first + 1..=holes {
+                    clauses.push(vec![-var(first, hole), -var(second, hole)]);
+                }
+            }
+        }
+        Clauses::from(clauses.as_slice())
+    }
+
+    #[test]
+    fn budget_interrupt() {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+
+        // far too hard to be solved before the flag is set
+        let clauses = pigeonhole(8);
+        let flag = Arc::new(AtomicBool::new(false));
+        let budget = Budget {
+            terminate: Some(Terminate::Flag(flag.clone())),
+            ..Default::default()
+        };
+        let setter = {
+            let flag = flag.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                flag.store(true, Ordering::Relaxed);
+            })
+        };
+        for res in solve_limited(&clauses, &budget) {
+            match res {
+                SolveResult::Unknown(Reason::Interrupted) => (),
+                res => panic!("{:?}", res),
+            }
+        }
+        setter.join().unwrap();
+    }
+
+    #[test]
+    fn budget_resume() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(46);
+        for _ in 0..10 {
+            let clauses = Clauses::from(random_3sat(&mut rng, 30, 128).as_slice());
+            let expected = dpll(&mut Cnf::from(clauses.clone())).is_ok();
+            // stop at the third check
+            let checks = Arc::new(AtomicUsize::new(0));
+            let callback = move || checks.fetch_add(1, Ordering::Relaxed) >= 2;
+            let budget = Budget {
+                terminate: Some(Terminate::Callback(Arc::new(callback))),
+                ..Default::default()
+            };
+
+            let mut cnf = Cnf::from(clauses.clone());
+            let res = dpll_limited(&mut cnf, &budget);
+            if res.is_unknown() {
+                assert!(matches!(res, SolveResult::Unknown(Reason::Interrupted)));
+                assert_eq!(
+                    dpll_limited(&mut cnf, &Budget::default()).is_sat(),
+                    expected
+                );
+            }
+            let mut cnf = CnfGraph::from(clauses.clone());
+            let res = cfcl_limited(&mut cnf, &budget);
+            if res.is_unknown() {
+                assert!(matches!(res, SolveResult::Unknown(Reason::Interrupted)));
+                match cfcl_limited(&mut cnf, &Budget::default()) {
+                    SolveResult::Sat(solution) => {
+                        assert!(expected);
+                        assert!(clauses.0.iter().all(|clause| clause
+                            .inner()
+                            .iter()
+                            .any(|&lit| solution.value(lit.var()) == Some(lit.is_positive()))));
+                    }
+                    res => assert!(res.is_unsat() && !expected),
+                }
+            }
+        }
+    }
+}
@@ -1,110 +1,275 @@
-use std::ops::Not;
+use std::time::Instant;
 
-use crate::{Clause, CnfGraph, PartialSolution};
+use rand::Rng;
+
+use crate::{
+    budget::{memory, Usage},
+    subsume::subsume_clauses,
+    vivify::vivify_learned,
+    Break, Budget, Clause, Clauses, CnfGraph, Heuristic, Lit, LocalSearch, NoObserver,
+    PartialSolution, SearchObserver, SolveResult, Terminate,
+};
+
+// the count of new learned clauses before they are subsumed and vivified again at the root
+const SUBSUME_INTERVAL: usize = 64;
+// the count of conflicts before the search restarts to vivify, when it did not reach the root
+const VIVIFY_INTERVAL: usize = 500;
+// the propagations that each vivification of the learned clauses may spend
+const VIVIFY_BUDGET: usize = 10_000;
+// the count of conflicts between two local searches that reset the saved phases
+const REPHASE_INTERVAL: usize = 100;
+// the flips that each of these local searches may spend
+const REPHASE_FLIPS: usize = 10_000;
+const REPHASE_HEURISTIC: Heuristic = Heuristic::ProbSat(Break::Polynomial { eps: 0.9, cb: 2.06 });
 
 pub fn cfcl(cnf: &mut CnfGraph) -> Result<(PartialSolution, &mut CnfGraph), usize> {
-    let mut solution = PartialSolution::new(cnf.n_lit);
+    let mut solution = PartialSolution::new(cnf.n_lit.max(cnf.max_lit));
     let mut learnt = vec![];
+    cnf.dot = None;
 
-    let res = _cfcl(cnf, &mut solution, &mut learnt).map(|res| (res, cnf));
+    let mut usage = Usage::new(cnf.progress);
 
-    println!("learned clauses: {:?}", learnt);
+    let res = _cfcl(
+        cnf,
+        &mut solution,
+        &mut learnt,
+        &Budget::default(),
+        &mut usage,
+        &mut NoObserver,
+    );
+    cnf.stats = usage.finish();
+    cnf.dot = usage.dot.take();
 
-    res
+    res.map(|res| (res, cnf))
+}
+
+// solve cnf until a limit of budget is reached or it is interrupted,
+// then cnf is back in the state it was given in, with the clauses learned so far, and can be
+// solved again
+pub fn cfcl_limited(cnf: &mut CnfGraph, budget: &Budget) -> SolveResult {
+    cfcl_observed(cnf, budget, &mut NoObserver)
 }
 
-fn propagate(cnf: &mut CnfGraph, solution: &mut PartialSolution) -> Result<(), usize> {
-    if cnf.clauses.is_empty() {
-        return Ok(());
+// solve cnf as cfcl_limited does, every event of the search is reported to observer
+pub fn cfcl_observed<O: SearchObserver>(
+    cnf: &mut CnfGraph,
+    budget: &Budget,
+    observer: &mut O,
+) -> SolveResult {
+    cnf.dot = None;
+    let root = cnf.clone();
+    let mut solution = PartialSolution::new(cnf.n_lit.max(cnf.max_lit));
+    let mut learned = vec![];
+    let mut usage = Usage::new(cnf.progress);
+    let res = _cfcl(
+        cnf,
+        &mut solution,
+        &mut learned,
+        budget,
+        &mut usage,
+        observer,
+    );
+    if let Ok(solution) = &res {
+        observer.on_solution(solution);
+    }
+    let res = usage.result(res);
+    if res.is_unknown() {
+        cnf.restore(root);
+        for clause in learned {
+            // an empty clause stays when the formula is unsatisfiable
+            let _ = cnf.add_assigned_clause(&clause);
+        }
     }
+    cnf.stats = usage.finish();
+    cnf.dot = usage.dot.take();
+    res
+}
 
-    // 1. try  unit propagation
-    let unit_lits = cnf.unit_propagations()?;
+fn propagate<O: SearchObserver>(
+    cnf: &mut CnfGraph,
+    solution: &mut PartialSolution,
+    terminate: Option<&Terminate>,
+    observer: &mut O,
+) -> Result<(), usize> {
+    // unit propagation only, every assigned lit must have a reason or be a guess
+    let unit_lits = cnf.unit_propagations_until(terminate, observer)?;
     for &lit in &unit_lits {
         solution.assign_lit(lit);
     }
+    Ok(())
+}
 
-    // 2. try pure literal elimination
-    let mut pure = vec![];
-    for lit in cnf.occurrences.keys() {
-        if cnf.occurrences.get(&lit.not()).is_none() {
-            pure.push(*lit);
-        }
-    }
-    for &lit in &pure {
+// every clause is satisfied, the remaining xor constraints are solved directly
+fn complete(cnf: &CnfGraph, solution: &mut PartialSolution) -> PartialSolution {
+    for lit in cnf.xors.model() {
         solution.assign_lit(lit);
-        cnf.propagation(lit)?;
     }
+    solution.clone()
+}
 
-    if cnf.occurrences.is_empty() {
-        if cnf.num_clause() == 0 {
-            return Ok(());
-        } else {
-            // conflict
-            return Err(usize::MAX);
+// local search on the original and the learned clauses, from the trail and then the saved phases,
+// its best assignment becomes the saved phases
+fn rephase(
+    original: &[Clause],
+    learned: &[Clause],
+    solution: &PartialSolution,
+    phases: &mut [Option<bool>],
+    seed: u64,
+) {
+    let mut clauses = Clauses::from([original, learned].concat());
+    clauses.2 = clauses.2.max(phases.len());
+    let mut start = PartialSolution::new(phases.len());
+    for (index, phase) in phases.iter().enumerate() {
+        if let Some(value) = phase {
+            start.assign_lit(Lit::from_index(index, *value));
+        }
+    }
+    for lit in solution.assigned() {
+        start.assign_lit(lit);
+    }
+    let mut search = LocalSearch::new(&clauses, seed);
+    search.set_phases(&start);
+    search.solve(REPHASE_HEURISTIC, REPHASE_FLIPS);
+    for lit in search.best().assigned() {
+        if let Some(phase) = phases.get_mut(lit.index()) {
+            *phase = Some(lit.is_positive());
         }
     }
-    Ok(())
 }
 
-fn _cfcl(
+fn _cfcl<O: SearchObserver>(
     cnf: &mut CnfGraph,
     solution: &mut PartialSolution,
     learned_clauses: &mut Vec<Clause>,
+    budget: &Budget,
+    usage: &mut Usage,
+    observer: &mut O,
 ) -> Result<PartialSolution, usize> {
-    propagate(cnf, solution)?;
-
-    // 3. now that we must make a guess
-    let guess_lit = match cnf.next_guess(crate::Strategy::Direct) {
-        Some(lit) => lit,
-        None => return Err(usize::MAX),
-    };
-
-    let mut _cnf = cnf.clone();
-    let mut _solution = solution.clone();
-
-    cnf.make_guess(guess_lit);
-    solution.assign_lit(guess_lit);
-    cnf.propagation(guess_lit)?;
-    if cnf.clauses.is_empty() && cnf.occurrences.is_empty() {
-        return Ok(solution.clone());
-    }
-
-    // 3.1. try lit is true
-    return match _cfcl(cnf, solution, learned_clauses) {
-        Ok(solution) => Ok(solution),
-        Err(clause_id) => {
-            // TODO: get the conflicted clause id and learn from it
-            // 3.2. try lit is false
-            if clause_id == usize::MAX {
+    // the state before each guess, with the count of learned clauses it already contains
+    let mut levels: Vec<(CnfGraph, PartialSolution, usize)> = vec![];
+    // the state without any learned clause, the count of learned clauses when they were last
+    // subsumed and the count of conflicts when they were last vivified
+    let root = (cnf.clone(), solution.clone());
+    let (mut subsumed, mut vivified) = (0, 0);
+    let mut ids = root.0.clauses.keys().cloned().collect::<Vec<_>>();
+    ids.sort();
+    let original = ids
+        .into_iter()
+        .map(|id| Clause(root.0.clauses[&id].0.all().cloned().collect()))
+        .collect::<Vec<_>>();
+    // the value that each var is guessed with, and the count of conflicts when they were last
+    // reset by local search
+    let mut phases = vec![None; solution.len()];
+    let mut rephased = 0;
+    // the lits of the clauses, for the memory of the clause database
+    let original_lits = original.iter().map(|clause| clause.0.len()).sum::<usize>();
+
+    loop {
+        let (assigned, start) = (cnf.trail.len(), Instant::now());
+        let mut conflict = propagate(cnf, solution, budget.terminate.as_ref(), observer).err();
+        usage.stats.propagations += cnf.trail.len() - assigned;
+        usage.stats.time.propagation += start.elapsed();
+        if conflict.is_none() {
+            if cnf.num_clause() == 0 {
+                return Ok(complete(cnf, solution));
+            }
+            // now that we must make a guess
+            let learned_lits = learned_clauses
+                .iter()
+                .map(|clause| clause.0.len())
+                .sum::<usize>();
+            usage.set_memory(memory(original_lits + learned_lits, levels.len() + 2));
+            if usage.exhausted(budget) {
                 return Err(usize::MAX);
             }
-            cnf.learn_from_conflict(clause_id)
-                .map(|c| learned_clauses.push(c));
-            *cnf = _cnf;
-            *solution = _solution;
-            let guess_not = guess_lit.not();
-            cnf.make_guess(guess_not);
-            cnf.propagation(guess_not)?;
-            solution.assign_lit(guess_not);
-            match _cfcl(cnf, solution, learned_clauses) {
-                Ok(res) => return Ok(res),
-                Err(clause_id) => {
-                    if clause_id == usize::MAX {
-                        return Err(usize::MAX);
-                    }
-                    cnf.learn_from_conflict(clause_id)
-                        .map(|c| learned_clauses.push(c));
-                    return Err(clause_id);
+            if usage.stats.conflicts >= rephased + REPHASE_INTERVAL {
+                let (seed, start) = (cnf.rng.gen(), Instant::now());
+                rephase(&original, learned_clauses, solution, &mut phases, seed);
+                rephased = usage.stats.conflicts;
+                usage.stats.time.local_search += start.elapsed();
+            }
+            let guess_lit = match cnf.next_guess(cnf.strategy) {
+                Some(lit) => match phases.get(lit.index()) {
+                    Some(&Some(value)) => Lit::from_index(lit.index(), value),
+                    _ => lit,
+                },
+                None => return Err(usize::MAX),
+            };
+            levels.push((cnf.clone(), solution.clone(), learned_clauses.len()));
+            cnf.make_guess(guess_lit);
+            observer.on_decision(guess_lit, cnf.level());
+            usage.stats.decisions += 1;
+            usage.stats.max_level = usage.stats.max_level.max(cnf.level());
+            solution.assign_lit(guess_lit);
+            let (assigned, start) = (cnf.trail.len() + 1, Instant::now());
+            conflict = cnf.propagation(guess_lit).err();
+            usage.stats.propagations += cnf.trail.len() - assigned;
+            usage.stats.time.propagation += start.elapsed();
+        }
+
+        // learn from the conflict and jump back to the level where the learned clause is unit
+        while let Some(clause_id) = conflict {
+            usage.conflict();
+            if cnf.dot_conflict == Some(usage.stats.conflicts) {
+                usage.dot = Some(cnf.implication_dot(clause_id));
+            }
+            observer.on_conflict(clause_id, &|| {
+                Clause(cnf.clauses[&clause_id].0.all().cloned().collect())
+            });
+            let start = Instant::now();
+            let learned = cnf.learn_from_conflict(clause_id);
+            usage.stats.time.analysis += start.elapsed();
+            let (learned, backjump) = match learned {
+                Some(res) => res,
+                None => return Err(clause_id),
+            };
+            if usage.exhausted(budget) {
+                return Err(clause_id);
+            }
+            observer.on_learned(&learned);
+            observer.on_backtrack(backjump);
+            learned_clauses.push(learned);
+            usage.stats.learned += 1;
+            levels.truncate(backjump + 1);
+            let (state, partial, mut seen) = levels.pop().unwrap();
+            let reduce = levels.is_empty() && learned_clauses.len() >= subsumed + SUBSUME_INTERVAL;
+            if reduce || usage.stats.conflicts >= vivified + VIVIFY_INTERVAL {
+                // back at the root, or restarting to it, the learned clauses are reduced and the
+                // state is rebuilt from the root without the ones that were removed
+                if !levels.is_empty() {
+                    levels.clear();
+                    observer.on_backtrack(0);
                 }
+                let start = Instant::now();
+                let (reduced, _) = subsume_clauses(learned_clauses);
+                let (reduced, _) = vivify_learned(&original, &reduced, VIVIFY_BUDGET);
+                usage.stats.deleted +=
+                    learned_clauses.len() - reduced.len().min(learned_clauses.len());
+                usage.stats.restarts += 1;
+                observer.on_restart();
+                usage.stats.time.reduction += start.elapsed();
+                *learned_clauses = reduced;
+                subsumed = learned_clauses.len();
+                vivified = usage.stats.conflicts;
+                cnf.restore(root.0.clone());
+                *solution = root.1.clone();
+                seen = 0;
+            } else {
+                cnf.restore(state);
+                *solution = partial;
             }
+            // add the clauses learned since the state was saved
+            conflict = learned_clauses[seen..]
+                .iter()
+                .find_map(|clause| cnf.add_assigned_clause(clause).err());
         }
-    };
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use crate::test_util::{planted, random_3sat};
     use crate::*;
 
     #[test]
@@ -140,9 +305,156 @@ mod tests {
         ];
         let clauses = Clauses::from(clauses.as_slice());
         let mut cnf = CnfGraph::from(clauses);
-        cfcl(&mut cnf);
+        cfcl(&mut cnf).unwrap();
         // println!("{}", solution.is_solved());
         // println!("{:?}", solution.true_lits());
         // println!("{:?}", solution.false_lits());
     }
+
+    #[test]
+    fn test_xor_conflict() {
+        // x1 ^ x2, x2 ^ x3 and x1 ^ x3 can not all be true
+        let clauses = vec![vec![1, 2, 3], vec![-1, -2, 4]];
+        let mut cnf = CnfGraph::from(Clauses::from(clauses.as_slice()));
+        cnf.add_xor(&[Lit::from_dimacs(1), Lit::from_dimacs(2)], true);
+        cnf.add_xor(&[Lit::from_dimacs(2), Lit::from_dimacs(3)], true);
+        assert!(cfcl(&mut cnf.clone()).is_ok());
+        cnf.add_xor(&[Lit::from_dimacs(1), Lit::from_dimacs(3)], true);
+        assert!(cfcl(&mut cnf).is_err());
+    }
+
+    // compare with all the assignments on small random formulas with xors
+    #[test]
+    fn test_random_with_xors() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        for _ in 0..200 {
+            let n = 6;
+            let m = rng.gen_range(4..14);
+            let clauses = random_3sat(&mut rng, n, m);
+            let xors = (0..rng.gen_range(0..3))
+                .map(|_| {
+                    let lits = (0..3)
+                        .map(|_| Lit::from_dimacs(rng.gen_range(1..=n) as isize))
+                        .collect::<Vec<_>>();
+                    (lits, rng.gen::<bool>())
+                })
+                .collect::<Vec<_>>();
+
+            let satisfies = |value: &dyn Fn(i32) -> bool| {
+                clauses.iter().all(|c| c.iter().any(|&l| value(l)))
+                    && xors.iter().all(|(lits, rhs)| {
+                        lits.iter()
+                            .fold(false, |acc, l| acc ^ value(l.to_dimacs() as i32))
+                            == *rhs
+                    })
+            };
+            let expected = (0..1u32 << n)
+                .any(|bits| satisfies(&|l: i32| (bits >> (l.abs() - 1) & 1 == 1) == (l > 0)));
+
+            let mut cnf = CnfGraph::from(Clauses::from(clauses.as_slice()));
+            for (lits, rhs) in &xors {
+                cnf.add_xor(lits, *rhs);
+            }
+            match cfcl(&mut cnf) {
+                Ok((solution, _)) => {
+                    let true_lits = solution.true_lits();
+                    let value =
+                        |l: i32| true_lits.contains(&(l.unsigned_abs() as usize - 1)) == (l > 0);
+                    assert!(expected);
+                    assert!(satisfies(&value), "{:?} {:?}", clauses, xors);
+                }
+                Err(_) => assert!(!expected, "{:?} {:?}", clauses, xors),
+            }
+
+            let mut cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+            for (lits, rhs) in &xors {
+                cnf.add_xor(lits, *rhs);
+            }
+            assert_eq!(dpll(&mut cnf).is_ok(), expected, "{:?} {:?}", clauses, xors);
+        }
+    }
+
+    // enough conflicts that the learned clauses are subsumed at the root several times
+    #[test]
+    fn test_random_subsume_learned() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(34);
+        let n = 50;
+        for _ in 0..10 {
+            let clauses = random_3sat(&mut rng, n, 213);
+            let expected = dpll(&mut Cnf::from(Clauses::from(clauses.as_slice()))).is_ok();
+            let mut cnf = CnfGraph::from(Clauses::from(clauses.as_slice()));
+            match cfcl(&mut cnf) {
+                Ok((solution, _)) => {
+                    assert!(expected, "{:?}", clauses);
+                    let true_lits = solution.true_lits();
+                    assert!(clauses.iter().all(|clause| clause.iter().any(|&l| {
+                        true_lits.contains(&(l.unsigned_abs() as usize - 1)) == (l > 0)
+                    })));
+                }
+                Err(_) => assert!(!expected, "{:?}", clauses),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rephase() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(43);
+        let clauses = planted(&mut rng, 30, 120);
+        let original = Clauses::from(clauses.as_slice()).0;
+        let mut phases = vec![None; 30];
+        // local search starts from the trail, here a single lit
+        let mut trail = PartialSolution::new(30);
+        trail.assign_lit(Lit::from_dimacs(-30));
+        super::rephase(&original, &[], &trail, &mut phases, 0);
+        assert!(phases.iter().all(|phase| phase.is_some()));
+        assert!(clauses.iter().all(|clause| clause
+            .iter()
+            .any(|&lit| phases[lit.unsigned_abs() as usize - 1] == Some(lit > 0))));
+    }
+
+    // enough conflicts that the phases are reset by local search
+    #[test]
+    fn test_random_rephase() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(43);
+        for _ in 0..3 {
+            let clauses = planted(&mut rng, 120, 510);
+            let mut cnf = CnfGraph::from(Clauses::from(clauses.as_slice()));
+            let (solution, _) = cfcl(&mut cnf).unwrap();
+            let true_lits = solution.true_lits();
+            assert!(clauses.iter().all(|clause| clause
+                .iter()
+                .any(|&l| true_lits.contains(&(l.unsigned_abs() as usize - 1)) == (l > 0))));
+        }
+    }
+
+    // the decisions of a seeded search with random guesses, on a new cnf every time
+    fn decisions(clauses: &[Vec<i32>], seed: u64) -> Vec<Lit> {
+        let mut cnf = CnfGraph::from(Clauses::from(clauses));
+        cnf.strategy = Strategy::Random;
+        cnf.set_seed(seed);
+        let _ = cfcl(&mut cnf);
+        cnf.decisions
+    }
+
+    #[test]
+    fn test_seed() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(44);
+        for _ in 0..3 {
+            let clauses = random_3sat(&mut rng, 50, 213);
+            let first = decisions(&clauses, 1);
+            assert!(!first.is_empty());
+            assert_eq!(decisions(&clauses, 1), first);
+            assert_ne!(decisions(&clauses, 2), first);
+        }
+    }
 }
@@ -42,7 +42,7 @@ impl From<&[Vec<i32>]> for Clauses {
         for clause in value {
             for &lit in clause {
                 vars_map.insert(lit.abs());
-                max = max.max(lit.abs() as usize);
+                max = max.max(lit.unsigned_abs() as usize);
             }
         }
         let mut clauses = Vec::new();
@@ -52,3 +52,14 @@ impl From<&[Vec<i32>]> for Clauses {
         Clauses(clauses, vars_map.len(), max)
     }
 }
+
+impl From<Vec<Clause>> for Clauses {
+    fn from(value: Vec<Clause>) -> Self {
+        let vars = value
+            .iter()
+            .flat_map(|clause| clause.0.iter().map(|lit| lit.var()))
+            .collect::<HashSet<_>>();
+        let max = vars.iter().map(|var| var.index() + 1).max().unwrap_or(0);
+        Clauses(value, vars.len(), max)
+    }
+}
@@ -1,8 +1,11 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 
-use rand::seq::IteratorRandom;
+use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
 
-use crate::{Clause, Clauses, Lit, Strategy};
+use crate::{
+    budget::TERMINATE_INTERVAL, Clause, Clauses, Lit, NoObserver, SearchObserver, Stats, Strategy,
+    Terminate, XorImplication, XorMatrix,
+};
 
 // record the cnf clauses and the state of propagation
 #[derive(Debug, Clone)]
@@ -11,18 +14,31 @@ pub struct Cnf {
     pub n_lit: usize,
     // count of clauses
     pub n_clause: usize,
-    pub clauses: HashMap<usize, HashSet<Lit>>,
-    pub occurrences: HashMap<Lit, HashSet<usize>>,
+    pub clauses: BTreeMap<usize, BTreeSet<Lit>>,
+    pub occurrences: BTreeMap<Lit, BTreeSet<usize>>,
     // units is a subset of clauses.keys()
     // and the clause in units should also be in clauses
-    pub units: HashSet<usize>,
+    pub units: BTreeSet<usize>,
+    // parity constraints, propagated alongside the clauses
+    pub xors: XorMatrix,
+    // id of the next added clause, ids are never reused
+    pub next_id: usize,
+    // every random choice of the search, seeded by `set_seed`
+    pub rng: StdRng,
+    // the guesses in the order they were made, going back to an earlier state keeps them
+    pub decisions: Vec<Lit>,
+    // the statistics of the last search
+    pub stats: Stats,
+    // the count of conflicts between two rows of the progress table that is logged, none when
+    // None
+    pub progress: Option<usize>,
     // for performance
     // shortest_clause_ids: HashSet<usize>,
 }
 
 impl From<Clauses> for Cnf {
     fn from(value: Clauses) -> Self {
-        let mut cnf = Cnf::new(value.1, value.0.len());
+        let mut cnf = Cnf::new(value.1, 0);
         for clause in value.0 {
             cnf.add_clause(clause);
         }
@@ -38,24 +54,47 @@ impl Cnf {
             clauses: Default::default(),
             occurrences: Default::default(),
             units: Default::default(),
+            xors: Default::default(),
+            next_id: 0,
+            rng: StdRng::seed_from_u64(0),
+            decisions: vec![],
+            stats: Stats::default(),
+            progress: None,
             // shortest_clause_ids: Default::default(),
         }
     }
 
+    // the same seed gives the same search
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // go back to a state saved before, the random choices and the decisions go on
+    pub(crate) fn restore(&mut self, state: Cnf) {
+        let rng = self.rng.clone();
+        let decisions = std::mem::take(&mut self.decisions);
+        *self = state;
+        self.rng = rng;
+        self.decisions = decisions;
+    }
+
     pub fn num_clause(&self) -> usize {
         self.clauses.len()
     }
 
-    pub fn add_clause(&mut self, clause: Clause) {
-        let clause = clause.inner().iter().cloned().collect::<HashSet<_>>();
-        let clause_id = self.clauses.len();
+    pub fn add_clause(&mut self, clause: Clause) -> usize {
+        let clause = clause.inner().iter().cloned().collect::<BTreeSet<_>>();
+        let clause_id = self.next_id;
+        self.next_id += 1;
+        self.n_clause += 1;
+        // the solution is indexed by var, so n_lit has to cover the vars that are skipped
+        for lit in clause.iter() {
+            self.n_lit = self.n_lit.max(lit.index() + 1);
+        }
 
         // 1. occurrences
         for lit in clause.iter() {
-            self.occurrences
-                .entry(*lit)
-                .or_insert_with(Default::default)
-                .insert(clause_id);
+            self.occurrences.entry(*lit).or_default().insert(clause_id);
         }
         // 2. units
         if clause.len() == 1 {
@@ -63,21 +102,51 @@ impl Cnf {
         }
         // 3. clauses
         self.clauses.insert(clause_id, clause);
+        clause_id
+    }
+
+    // add the constraint `lits[0] ^ lits[1] ^ ... == rhs`
+    pub fn add_xor(&mut self, lits: &[Lit], rhs: bool) {
+        for lit in lits {
+            self.n_lit = self.n_lit.max(lit.index() + 1);
+        }
+        self.xors.add_xor(lits, rhs);
+        // a conflict stays in the cnf as an empty clause
+        let _ = self.propagate_xors();
+    }
+
+    // eliminate the xor matrix and turn what it derives into clauses:
+    // an implied lit becomes a unit clause, a conflict an empty clause
+    fn propagate_xors(&mut self) -> Result<(), usize> {
+        for implication in self.xors.eliminate() {
+            match implication {
+                XorImplication::Unit(lit, _) => {
+                    self.add_clause(Clause(vec![lit]));
+                }
+                XorImplication::Conflict(_) => return Err(self.add_clause(Clause(vec![]))),
+            }
+        }
+        Ok(())
     }
 
     // the clause of clause_id is unit
     // so it must be true, and we can do propagation based on that
     pub fn unit_propagation(&mut self, clause_id: usize) -> Result<Option<Lit>, usize> {
+        self.unit_propagation_observed(clause_id, &mut NoObserver)
+    }
+
+    // unit propagation that reports the lit to observer before it is propagated
+    fn unit_propagation_observed<O: SearchObserver>(
+        &mut self,
+        clause_id: usize,
+        observer: &mut O,
+    ) -> Result<Option<Lit>, usize> {
         if let Some(clause) = self.clauses.remove(&clause_id) {
             self.n_clause -= 1;
             assert!(clause.len() == 1, "{:?}", clause);
             let lit: Lit = clause.into_iter().next().unwrap();
-            log::debug!(
-                "after unit_propagation of clause {}, lit: {}: {:?}",
-                clause_id,
-                lit,
-                self
-            );
+            log::trace!("unit propagation of clause {}, lit: {}", clause_id, lit);
+            observer.on_propagation(lit, Some(clause_id));
 
             return self.propagation(lit).map(|_| Some(lit));
         }
@@ -85,10 +154,25 @@ impl Cnf {
     }
 
     pub fn unit_propagations(&mut self) -> Result<Vec<Lit>, usize> {
+        self.unit_propagations_until(None, &mut NoObserver)
+    }
+
+    // unit propagation that stops early once terminate is set,
+    // the units that are left are propagated by the next call
+    pub(crate) fn unit_propagations_until<O: SearchObserver>(
+        &mut self,
+        terminate: Option<&Terminate>,
+        observer: &mut O,
+    ) -> Result<Vec<Lit>, usize> {
         let mut lits = Vec::new();
         while !self.units.is_empty() {
-            let clause_id = self.units.iter().next().unwrap().clone();
-            if let Some(lit) = self.unit_propagation(clause_id)? {
+            if lits.len() % TERMINATE_INTERVAL == TERMINATE_INTERVAL - 1
+                && terminate.is_some_and(|terminate| terminate.is_set())
+            {
+                break;
+            }
+            let clause_id = *self.units.iter().next().unwrap();
+            if let Some(lit) = self.unit_propagation_observed(clause_id, observer)? {
                 lits.push(lit);
             }
             self.units.remove(&clause_id);
@@ -100,7 +184,12 @@ impl Cnf {
     // simplify the clauses that contains lit or !lit
     pub fn propagation(&mut self, lit: Lit) -> Result<(), usize> {
         self.remove_positive(lit);
-        self.remove_negation(!lit)
+        self.remove_negation(!lit)?;
+        if self.xors.contains(lit.var()) {
+            self.xors.assign(lit);
+            self.propagate_xors()?;
+        }
+        Ok(())
     }
 
     pub fn remove_positive(&mut self, lit: Lit) {
@@ -134,7 +223,7 @@ impl Cnf {
                     clause.remove(&lit);
                     // 2. units
                     // one clause is conflict when all the lits in the clause are false
-                    if clause.len() == 0 {
+                    if clause.is_empty() {
                         return Err(clause_id);
                     } else if clause.len() == 1 {
                         self.units.insert(clause_id);
@@ -179,8 +268,7 @@ impl Cnf {
     // 2. after choose the lit, we can do more unit propagation
     pub fn next_guess(&mut self, _strategy: Strategy) -> Option<Lit> {
         // vanilla strategy
-        let keys = self.occurrences.keys().cloned().collect::<Vec<_>>();
-        return keys.iter().choose(&mut rand::thread_rng()).cloned();
+        self.occurrences.keys().choose(&mut self.rng).cloned()
     }
 }
 
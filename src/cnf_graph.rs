@@ -1,23 +1,26 @@
 use std::{
-    collections::{BTreeSet, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet},
     ops::Not,
 };
 
 use petgraph::prelude::NodeIndex;
-use rand::seq::IteratorRandom;
+use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
 
-use crate::{Clause, Clauses, Lit, Strategy};
+use crate::{
+    budget::TERMINATE_INTERVAL, Clause, Clauses, Lit, NoObserver, SearchObserver, Stats, Strategy,
+    Terminate, Var, XorImplication, XorMatrix,
+};
 
 #[derive(Debug, Clone, Default)]
 pub struct FakeHashSet {
-    inner: HashMap<Lit, bool>,
+    inner: BTreeMap<Lit, bool>,
     num: usize,
 }
 
 impl FakeHashSet {
     pub fn new() -> Self {
         FakeHashSet {
-            inner: HashMap::new(),
+            inner: BTreeMap::default(),
             num: 0,
         }
     }
@@ -44,7 +47,7 @@ impl FakeHashSet {
         self.num == 0
     }
 
-    pub fn from_set(set: &HashSet<Lit>) -> Self {
+    pub fn from_set(set: &BTreeSet<Lit>) -> Self {
         let mut fake_set = FakeHashSet::new();
         for lit in set {
             fake_set.insert(*lit);
@@ -60,23 +63,32 @@ impl FakeHashSet {
     }
 
     pub fn all(&self) -> impl Iterator<Item = &Lit> {
-        self.inner.iter().map(|(k, _)| k)
+        self.inner.keys()
     }
 }
 
-impl From<FakeHashSet> for HashSet<Lit> {
+impl From<FakeHashSet> for BTreeSet<Lit> {
     fn from(value: FakeHashSet) -> Self {
         value
             .inner
             .iter()
             .filter(|(_, &valid)| valid)
-            .map(|(k, _)| k.clone())
+            .map(|(k, _)| *k)
             .collect()
     }
 }
 
 use petgraph::graph::DiGraph;
 
+// the value of an assigned var, the decision level it was assigned at,
+// and the clause that implied it (None for guesses)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assignment {
+    pub value: bool,
+    pub level: usize,
+    pub reason: Option<usize>,
+}
+
 // record the cnf clauses and the state of propagation
 #[derive(Debug, Clone)]
 pub struct CnfGraph {
@@ -86,23 +98,46 @@ pub struct CnfGraph {
     // count of clauses
     pub n_clause: usize,
     // set of lits && is_valid
-    pub clauses: HashMap<usize, (FakeHashSet, bool)>,
-    pub occurrences: HashMap<Lit, HashSet<usize>>,
+    pub clauses: BTreeMap<usize, (FakeHashSet, bool)>,
+    pub occurrences: BTreeMap<Lit, BTreeSet<usize>>,
     // units is a subset of clauses.keys()
     // and the clause in units should also be in clauses
-    pub units: HashSet<usize>,
-
-    // update the graph when add unit clause into the units
-    // but ignore the buildup process
+    pub units: BTreeSet<usize>,
+    // parity constraints, what they imply is added as clauses so that it has a reason
+    pub xors: XorMatrix,
+    // id of the next added clause, ids are never reused
+    pub next_id: usize,
+
+    // the implication graph: an edge from every antecedent to the lit it implied,
+    // weighted by the id of the reason clause
     pub graph: DiGraph<Lit, usize>,
-    // lit.index() -> node
+    // lit.code() -> node
     pub nodes: Vec<NodeIndex>,
     pub guessed: Vec<Lit>,
+    // assigned lits in the order of assignment
+    pub trail: Vec<Lit>,
+    pub assignments: BTreeMap<Var, Assignment>,
+
+    // how cfcl guesses
+    pub strategy: Strategy,
+    // every random choice of the search, seeded by `set_seed`
+    pub rng: StdRng,
+    // the guesses in the order they were made, going back to an earlier state keeps them
+    pub decisions: Vec<Lit>,
+    // the statistics of the last search
+    pub stats: Stats,
+    // the count of conflicts between two rows of the progress table that is logged, none when
+    // None
+    pub progress: Option<usize>,
+    // the number of the conflict, from 1, whose implication graph cfcl keeps in dot
+    pub dot_conflict: Option<usize>,
+    // the implication graph at that conflict of the last search, see `implication_dot`
+    pub dot: Option<String>,
 }
 
 impl From<Clauses> for CnfGraph {
     fn from(value: Clauses) -> Self {
-        let mut cnf = CnfGraph::new(value.1, value.2, value.0.len());
+        let mut cnf = CnfGraph::new(value.1, value.2, 0);
         for clause in value.0 {
             cnf.add_clause(clause);
         }
@@ -119,25 +154,72 @@ impl CnfGraph {
             clauses: Default::default(),
             occurrences: Default::default(),
             units: Default::default(),
+            xors: Default::default(),
+            next_id: 0,
             graph: DiGraph::new(),
             nodes: vec![NodeIndex::end(); 2 * max_lit + 2],
             guessed: Default::default(),
+            trail: Default::default(),
+            assignments: Default::default(),
+            strategy: Strategy::Direct,
+            rng: StdRng::seed_from_u64(0),
+            decisions: vec![],
+            stats: Stats::default(),
+            progress: None,
+            dot_conflict: None,
+            dot: None,
         }
     }
+
+    // the same seed gives the same search
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // go back to a state saved before, the random choices and the decisions go on
+    pub(crate) fn restore(&mut self, state: CnfGraph) {
+        let rng = self.rng.clone();
+        let decisions = std::mem::take(&mut self.decisions);
+        *self = state;
+        self.rng = rng;
+        self.decisions = decisions;
+    }
+
     pub fn num_clause(&self) -> usize {
         self.clauses.values().filter(|(_, valid)| *valid).count()
     }
 
-    pub fn add_clause(&mut self, clause: Clause) {
-        let clause = clause.inner().iter().cloned().collect::<HashSet<_>>();
-        let clause_id = self.clauses.len();
+    // the current decision level
+    pub fn level(&self) -> usize {
+        self.guessed.len()
+    }
+
+    pub fn value(&self, lit: Lit) -> Option<bool> {
+        self.assignments
+            .get(&lit.var())
+            .map(|assignment| assignment.value == lit.is_positive())
+    }
+
+    // make room for the var of lit in max_lit and the nodes
+    fn reserve(&mut self, lit: Lit) {
+        self.max_lit = self.max_lit.max(lit.index() + 1);
+        if self.nodes.len() < 2 * self.max_lit + 2 {
+            self.nodes.resize(2 * self.max_lit + 2, NodeIndex::end());
+        }
+    }
+
+    pub fn add_clause(&mut self, clause: Clause) -> usize {
+        let clause = clause.inner().iter().cloned().collect::<BTreeSet<_>>();
+        let clause_id = self.next_id;
+        self.next_id += 1;
+        self.n_clause += 1;
+        for &lit in clause.iter() {
+            self.reserve(lit);
+        }
 
         // 1. occurrences
         for lit in clause.iter() {
-            self.occurrences
-                .entry(*lit)
-                .or_insert_with(Default::default)
-                .insert(clause_id);
+            self.occurrences.entry(*lit).or_default().insert(clause_id);
         }
         // 2. units
         if clause.len() == 1 {
@@ -146,35 +228,112 @@ impl CnfGraph {
         // 3. clauses
         self.clauses
             .insert(clause_id, (FakeHashSet::from_set(&clause), true));
+        clause_id
+    }
+
+    // add a clause to the current state of propagation:
+    // the lits that are already false are removed from it right away,
+    // but they still belong to the clause when it is used as a reason
+    // return Ok(None): the clause is satisfied and is not added
+    // return Err(clause_id): all the lits of the clause are false
+    pub fn add_assigned_clause(&mut self, clause: &Clause) -> Result<Option<usize>, usize> {
+        if clause
+            .inner()
+            .iter()
+            .any(|&lit| self.value(lit) == Some(true))
+        {
+            return Ok(None);
+        }
+        let free = clause
+            .inner()
+            .iter()
+            .filter(|&&lit| self.value(lit).is_none())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let clause_id = self.add_clause(Clause(free.iter().cloned().collect()));
+        let (lits, _) = self.clauses.get_mut(&clause_id).unwrap();
+        for &lit in clause.inner() {
+            if !free.contains(&lit) {
+                lits.insert(lit);
+                lits.remove(lit);
+            }
+        }
+        if free.is_empty() {
+            return Err(clause_id);
+        }
+        Ok(Some(clause_id))
+    }
+
+    // add the constraint `lits[0] ^ lits[1] ^ ... == rhs`
+    pub fn add_xor(&mut self, lits: &[Lit], rhs: bool) {
+        for &lit in lits {
+            self.reserve(lit);
+        }
+        self.xors.add_xor(lits, rhs);
+        // a conflict stays in the cnf as an empty clause
+        let _ = self.propagate_xors();
+    }
+
+    // eliminate the xor matrix, what it derives is added as its reason clause:
+    // the clause is unit for an implied lit and empty for a conflict
+    fn propagate_xors(&mut self) -> Result<(), usize> {
+        for implication in self.xors.eliminate() {
+            match implication {
+                XorImplication::Unit(_, reason) | XorImplication::Conflict(reason) => {
+                    self.add_assigned_clause(&reason)?;
+                }
+            }
+        }
+        Ok(())
     }
 
     // the clause of clause_id is unit
     // so it must be true, and we can do propagation based on that
     pub fn unit_propagation(&mut self, clause_id: usize) -> Result<Option<Lit>, usize> {
+        self.unit_propagation_observed(clause_id, &mut NoObserver)
+    }
+
+    // unit propagation that reports the lit to observer before it is propagated
+    fn unit_propagation_observed<O: SearchObserver>(
+        &mut self,
+        clause_id: usize,
+        observer: &mut O,
+    ) -> Result<Option<Lit>, usize> {
         if let Some((clause, valid)) = self.clauses.get_mut(&clause_id) {
             if *valid {
                 *valid = false;
                 self.n_clause -= 1;
                 assert!(clause.len() == 1, "{:?}", clause);
                 let lit: Lit = clause.iter().next().cloned().unwrap();
-                log::debug!(
-                    "after unit_propagation of clause {}, lit: {}: {:?}",
-                    clause_id,
-                    lit,
-                    self
-                );
-
-                return self.propagation(lit).map(|_| Some(lit));
+                log::trace!("unit propagation of clause {}, lit: {}", clause_id, lit);
+                observer.on_propagation(lit, Some(clause_id));
+
+                return self.assign(lit, Some(clause_id)).map(|_| Some(lit));
             }
         }
         Ok(None)
     }
 
     pub fn unit_propagations(&mut self) -> Result<Vec<Lit>, usize> {
+        self.unit_propagations_until(None, &mut NoObserver)
+    }
+
+    // unit propagation that stops early once terminate is set,
+    // the units that are left are propagated by the next call
+    pub(crate) fn unit_propagations_until<O: SearchObserver>(
+        &mut self,
+        terminate: Option<&Terminate>,
+        observer: &mut O,
+    ) -> Result<Vec<Lit>, usize> {
         let mut lits = Vec::new();
         while !self.units.is_empty() {
-            let clause_id = self.units.iter().next().unwrap().clone();
-            if let Some(lit) = self.unit_propagation(clause_id)? {
+            if lits.len() % TERMINATE_INTERVAL == TERMINATE_INTERVAL - 1
+                && terminate.is_some_and(|terminate| terminate.is_set())
+            {
+                break;
+            }
+            let clause_id = *self.units.iter().next().unwrap();
+            if let Some(lit) = self.unit_propagation_observed(clause_id, observer)? {
                 lits.push(lit);
             }
             self.units.remove(&clause_id);
@@ -185,16 +344,49 @@ impl CnfGraph {
     // based on lit is true
     // simplify the clauses that contains lit or !lit
     pub fn propagation(&mut self, lit: Lit) -> Result<(), usize> {
+        self.assign(lit, None)
+    }
+
+    fn node(&mut self, lit: Lit) -> NodeIndex {
         if self.nodes[lit.code()] == NodeIndex::end() {
             self.nodes[lit.code()] = self.graph.add_node(lit);
         }
-        self.remove_positive(lit);
+        self.nodes[lit.code()]
+    }
 
-        let lit = !lit;
-        if self.nodes[lit.code()] == NodeIndex::end() {
-            self.nodes[lit.code()] = self.graph.add_node(lit);
+    // record lit on the trail, with an edge from each of its antecedents in the graph
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) -> Result<(), usize> {
+        self.trail.push(lit);
+        self.assignments.insert(
+            lit.var(),
+            Assignment {
+                value: lit.is_positive(),
+                level: self.level(),
+                reason,
+            },
+        );
+        let lit_node = self.node(lit);
+        self.node(!lit);
+        if let Some(clause_id) = reason {
+            let antecedents = self.clauses[&clause_id]
+                .0
+                .all()
+                .filter(|&&other| other != lit)
+                .map(|&other| !other)
+                .collect::<Vec<_>>();
+            for antecedent in antecedents {
+                let antecedent_node = self.node(antecedent);
+                self.graph.add_edge(antecedent_node, lit_node, clause_id);
+            }
+        }
+
+        self.remove_positive(lit);
+        self.remove_negation(!lit)?;
+        if self.xors.contains(lit.var()) {
+            self.xors.assign(lit);
+            self.propagate_xors()?;
         }
-        self.remove_negation(lit)
+        Ok(())
     }
 
     pub fn remove_positive(&mut self, lit: Lit) {
@@ -223,40 +415,13 @@ impl CnfGraph {
     pub fn remove_negation(&mut self, lit: Lit) -> Result<(), usize> {
         // 1. occurrences
         if let Some(occurs) = self.occurrences.remove(&lit) {
-            // the clauses that is useless since the lit is true
-            // println!("update graph, lit: {:?}", lit.not());
             for clause_id in occurs {
-                if let Some((mut clause, _)) = self.clauses.remove(&clause_id) {
-                    // update the occurrences for other lits in this clause since this clause is removed
-                    // 3.1 clause
+                if let Some((clause, _)) = self.clauses.get_mut(&clause_id) {
+                    // 2. clause
                     clause.remove(lit);
-                    // update the graph
-                    let lit_node = self.nodes[lit.not().code()];
-                    if lit_node == NodeIndex::end() {
-                        panic!("lit_node: {:?}", lit_node);
-                    }
-                    for &affected in clause.iter() {
-                        let mut affected_node = self.nodes[affected.code()];
-                        if affected_node == NodeIndex::end() {
-                            let n = self.graph.add_node(affected);
-                            self.nodes[affected.code()] = n;
-                            affected_node = n;
-                        }
-                        if !self.graph.contains_edge(lit_node, affected_node) {
-                            // println!(
-                            //     "add edge: {:?} -> {:?}, clause id:{}",
-                            //     lit, affected, clause_id
-                            // );
-                            self.graph.add_edge(lit_node, affected_node, 1);
-                        }
-                    }
-
-                    // clause
-                    self.clauses.insert(clause_id, (clause.clone(), true));
-
-                    // units
+                    // 3. units
                     // one clause is conflict when all the lits in the clause are false
-                    if clause.len() == 0 {
+                    if clause.is_empty() {
                         return Err(clause_id);
                     } else if clause.len() == 1 {
                         self.units.insert(clause_id);
@@ -308,62 +473,86 @@ impl CnfGraph {
                     .next()
                     .map(|lit| if lit.is_negative() { lit.not() } else { *lit });
 
-                return res;
-            }
-            Strategy::Random => {
-                let keys = self.occurrences.keys().cloned().collect::<Vec<_>>();
-                return keys.iter().choose(&mut rand::thread_rng()).cloned();
+                res
             }
+            Strategy::Random => self.occurrences.keys().choose(&mut self.rng).cloned(),
         }
     }
 
     pub fn make_guess(&mut self, lit: Lit) {
         self.guessed.push(lit);
+        self.decisions.push(lit);
     }
 
-    pub fn learn_from_conflict(&mut self, clause_id: usize) -> Option<Clause> {
-        // let mut clause = Vec::new();
-        println!("clause id:{}", clause_id);
-        println!("graph: {:?}", self.graph);
-        println!(
-            "guessed:{:?}, conflict clause: {:?}",
+    // first uip conflict analysis
+    // resolve the conflict clause with the reasons of its lits, latest assigned first,
+    // until only one lit of the current level is left
+    // return the learned clause, with the lit of the current level first,
+    // and the level to backjump to, where the learned clause is unit
+    // return None: the conflict does not depend on any guess
+    pub fn learn_from_conflict(&mut self, clause_id: usize) -> Option<(Clause, usize)> {
+        let (learned, _) = self.analyze(clause_id)?;
+        let backjump = learned[1..]
+            .iter()
+            .map(|lit| self.assignments[&lit.var()].level)
+            .max()
+            .unwrap_or(0);
+        Some((Clause(learned), backjump))
+    }
+
+    // the first uip analysis of learn_from_conflict
+    // return the lits of the learned clause, the negation of the uip first,
+    // and the lits of the current level that were resolved away, latest assigned first:
+    // with the conflict they are the side of the first uip cut that the uip implies
+    pub(crate) fn analyze(&self, clause_id: usize) -> Option<(Vec<Lit>, Vec<Lit>)> {
+        let level = self.level();
+        if level == 0 {
+            return None;
+        }
+        log::trace!(
+            "conflict of clause {}, guessed: {:?}, conflict clause: {:?}",
+            clause_id,
             self.guessed,
             self.clauses[&clause_id].0.all().collect::<Vec<_>>()
         );
 
-        let root = self.guessed[0];
-        let mut special = self.guessed.iter().cloned().collect::<HashSet<_>>();
-        special.remove(&root);
-        let mut queue = self.clauses[&clause_id]
-            .0
-            .all()
-            .map(|x| x.not())
-            .collect::<HashSet<_>>();
-        queue.remove(&root);
-
-        // must have a root
-        let mut learned = HashSet::from([root]);
-        while !queue.is_empty() {
-            let lit = queue.iter().next().unwrap().clone();
-            queue.remove(&lit);
-            if learned.contains(&lit) {
-                continue;
+        let mut seen = BTreeSet::default();
+        let mut learned = vec![];
+        let mut resolved = vec![];
+        // count of the seen lits of the current level that are not resolved yet
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut reason = clause_id;
+        loop {
+            for &lit in self.clauses[&reason].0.all() {
+                let assignment = self.assignments[&lit.var()];
+                if assignment.level == 0 || !seen.insert(lit.var()) {
+                    continue;
+                }
+                if assignment.level == level {
+                    pending += 1;
+                } else {
+                    learned.push(lit);
+                }
             }
-            let lit_node = self.nodes[lit.code()];
-            let parents = self
-                .graph
-                .neighbors_directed(lit_node, petgraph::Direction::Incoming);
-            let parents = parents.map(|parent| self.graph[parent]).collect::<Vec<_>>();
-            if parents.iter().any(|x| special.contains(x)) {
-                learned.insert(lit);
-            } else {
-                queue.extend(parents.iter().cloned());
+            // the latest assigned lit that takes part in the conflict
+            let lit = loop {
+                index -= 1;
+                if seen.contains(&self.trail[index].var()) {
+                    break self.trail[index];
+                }
+            };
+            pending -= 1;
+            if pending == 0 {
+                learned.insert(0, lit.not());
+                break;
             }
+            resolved.push(lit);
+            reason = self.assignments[&lit.var()]
+                .reason
+                .expect("only the guess of a level has no reason");
         }
-        let learned = learned.into_iter().map(|x| x.not()).collect::<Vec<_>>();
-        // println!("learnt clauses: {:?}", learned);
-
-        return Some(Clause(learned));
+        Some((learned, resolved))
     }
 }
 
@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use num_bigint::BigUint;
+
+use crate::{dpll, Clause, Clauses, Cnf, Lit, Var};
+
+// a residual formula with sorted lits and sorted clauses,
+// so that equal components have equal keys in the cache
+pub(crate) type Component = Vec<Vec<Lit>>;
+
+/// The exact number of models of clauses.
+///
+/// Without a projection the models are counted over all the vars up to the largest one that
+/// occurs, a var that does not occur in any clause (anymore) doubles the count. With a projection
+/// the number of distinct assignments to the given vars that extend to a model is counted.
+///
+/// The residual formula is split into components that share no vars, the count of each component
+/// is cached and the counts of the components are multiplied.
+pub fn count(clauses: &Clauses, projection: Option<&[Var]>) -> BigUint {
+    let formula = clauses
+        .0
+        .iter()
+        .map(|clause| clause.inner().to_vec())
+        .collect();
+    let vars = match projection {
+        Some(vars) => vars.iter().cloned().collect(),
+        None => (0..clauses.2).map(Var::from_index).collect(),
+    };
+    let formula = normalize(formula);
+    if formula.iter().any(|clause| clause.is_empty()) {
+        return BigUint::from(0u32);
+    }
+    let mut counter = Counter {
+        projection: projection.map(|vars| vars.iter().cloned().collect()),
+        cache: HashMap::new(),
+    };
+    counter.count(formula, vars)
+}
+
+struct Counter {
+    projection: Option<HashSet<Var>>,
+    cache: HashMap<Component, BigUint>,
+}
+
+impl Counter {
+    fn is_projected(&self, var: Var) -> bool {
+        match &self.projection {
+            Some(projection) => projection.contains(&var),
+            None => true,
+        }
+    }
+
+    // count the models of clauses over vars, the projected vars that are not in clauses are free
+    fn count(&mut self, mut clauses: Vec<Vec<Lit>>, mut vars: BTreeSet<Var>) -> BigUint {
+        if !self.simplify(&mut clauses, &mut vars) {
+            return BigUint::from(0u32);
+        }
+        let occurring = clauses
+            .iter()
+            .flatten()
+            .map(|lit| lit.var())
+            .collect::<HashSet<_>>();
+        let free = vars.iter().filter(|var| !occurring.contains(var)).count();
+
+        let mut res = BigUint::from(1u32) << free;
+        for component in components(clauses) {
+            res *= self.count_component(component);
+            if res.bits() == 0 {
+                break;
+            }
+        }
+        res
+    }
+
+    fn count_component(&mut self, mut component: Component) -> BigUint {
+        for clause in component.iter_mut() {
+            clause.sort();
+        }
+        component.sort();
+        if let Some(res) = self.cache.get(&component) {
+            return res.clone();
+        }
+
+        // branch on the projected var that occurs the most
+        let mut occurs = HashMap::<Var, usize>::new();
+        for lit in component.iter().flatten() {
+            if self.is_projected(lit.var()) {
+                *occurs.entry(lit.var()).or_default() += 1;
+            }
+        }
+        let res = match occurs.iter().max_by_key(|(var, n)| (**n, *var)) {
+            Some((&var, _)) => {
+                let vars = occurs
+                    .keys()
+                    .filter(|&&other| other != var)
+                    .cloned()
+                    .collect::<BTreeSet<_>>();
+                let mut res = BigUint::from(0u32);
+                for lit in [var.positive(), var.negative()] {
+                    if let Some(clauses) = condition(&component, lit) {
+                        res += self.count(clauses, vars.clone());
+                    }
+                }
+                res
+            }
+            // only vars that are projected away are left, one model is enough
+            None => {
+                let mut cnf = Cnf::new(0, 0);
+                for clause in component.iter() {
+                    cnf.add_clause(Clause(clause.clone()));
+                }
+                BigUint::from(dpll(&mut cnf).is_ok() as u32)
+            }
+        };
+        self.cache.insert(component, res.clone());
+        res
+    }
+
+    // unit propagation, the assigned vars are not free anymore
+    // a pure lit of a var that is projected away can be set without losing models,
+    // but the other pure lits have to be counted both ways
+    // return false: there is a conflict
+    fn simplify(&self, clauses: &mut Vec<Vec<Lit>>, vars: &mut BTreeSet<Var>) -> bool {
+        loop {
+            let unit = clauses
+                .iter()
+                .find(|clause| clause.len() == 1)
+                .map(|clause| clause[0]);
+            let pure = || {
+                let lits = clauses.iter().flatten().cloned().collect::<BTreeSet<_>>();
+                lits.iter()
+                    .find(|lit| !self.is_projected(lit.var()) && !lits.contains(&!**lit))
+                    .cloned()
+            };
+            let lit = match unit.or_else(pure) {
+                Some(lit) => lit,
+                None => return true,
+            };
+            vars.remove(&lit.var());
+            match condition(clauses, lit) {
+                Some(res) => *clauses = res,
+                None => return false,
+            }
+        }
+    }
+}
+
+// remove duplicated lits and tautologies
+pub(crate) fn normalize(clauses: Vec<Vec<Lit>>) -> Vec<Vec<Lit>> {
+    clauses
+        .into_iter()
+        .map(|clause| {
+            clause
+                .into_iter()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>()
+        })
+        .filter(|clause| !clause.iter().any(|lit| clause.contains(&!*lit)))
+        .collect()
+}
+
+// simplify clauses based on lit is true
+// return None: a clause becomes empty
+pub(crate) fn condition(clauses: &[Vec<Lit>], lit: Lit) -> Option<Vec<Vec<Lit>>> {
+    let mut res = Vec::new();
+    for clause in clauses {
+        if clause.contains(&lit) {
+            continue;
+        }
+        let clause = clause
+            .iter()
+            .filter(|&&other| other != !lit)
+            .cloned()
+            .collect::<Vec<_>>();
+        if clause.is_empty() {
+            return None;
+        }
+        res.push(clause);
+    }
+    Some(res)
+}
+
+// split the clauses into groups that share no vars, none of the clauses may be empty
+pub(crate) fn components(clauses: Vec<Vec<Lit>>) -> Vec<Component> {
+    // union find over the vars
+    let mut parent = HashMap::<Var, Var>::new();
+    fn find(parent: &mut HashMap<Var, Var>, var: Var) -> Var {
+        let up = *parent.entry(var).or_insert(var);
+        if up == var {
+            return var;
+        }
+        let root = find(parent, up);
+        parent.insert(var, root);
+        root
+    }
+    for clause in clauses.iter() {
+        let first = find(&mut parent, clause[0].var());
+        for lit in clause[1..].iter() {
+            let root = find(&mut parent, lit.var());
+            parent.insert(root, first);
+        }
+    }
+
+    // in the order of the roots, the same clauses always give the same components
+    let mut groups = BTreeMap::<Var, Component>::new();
+    for clause in clauses {
+        let root = find(&mut parent, clause[0].var());
+        groups.entry(root).or_default().push(clause);
+    }
+    groups.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use num_bigint::BigUint;
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{random_lit, satisfies};
+    use crate::*;
+
+    #[test]
+    fn count_small() {
+        // the pure lits 1 and 2 still count both ways, var 3 does not occur
+        let clauses = vec![vec![1, 2], vec![4]];
+        let clauses = Clauses::from(clauses.as_slice());
+        assert_eq!(count(&clauses, None), BigUint::from(6u32));
+
+        let clauses = vec![vec![1], vec![-1]];
+        let clauses = Clauses::from(clauses.as_slice());
+        assert_eq!(count(&clauses, None), BigUint::from(0u32));
+
+        let clauses = vec![vec![1, 2], vec![]];
+        let clauses = Clauses::from(clauses.as_slice());
+        assert_eq!(count(&clauses, None), BigUint::from(0u32));
+    }
+
+    #[test]
+    fn count_components() {
+        // 100 independent copies of x || y, far more models than fit in a u64
+        let clauses = (0..100)
+            .map(|i| vec![2 * i + 1, 2 * i + 2])
+            .collect::<Vec<_>>();
+        let clauses = Clauses::from(clauses.as_slice());
+        assert_eq!(count(&clauses, None), BigUint::from(3u32).pow(100));
+    }
+
+    #[test]
+    fn count_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(28);
+        let n = 8;
+        for _ in 0..300 {
+            let clauses = (0..rng.gen_range(1..16))
+                .map(|_| {
+                    (0..rng.gen_range(1..4))
+                        .map(|_| random_lit(&mut rng, n))
+                        .collect()
+                })
+                .collect::<Vec<Vec<i32>>>();
+            let models = (0..1u32 << n)
+                .filter(|&bits| satisfies(&clauses, bits))
+                .collect::<Vec<_>>();
+            // only the vars up to the largest one are counted
+            let max = clauses.iter().flatten().map(|l| l.abs()).max().unwrap();
+            let formula = Clauses::from(clauses.as_slice());
+            assert_eq!(
+                count(&formula, None),
+                BigUint::from(models.len() >> (n - max)),
+                "{:?}",
+                clauses
+            );
+
+            let projection = [1, 3, 4].map(Var::from_dimacs);
+            let projected = models
+                .iter()
+                .map(|bits| bits & 0b1101)
+                .collect::<std::collections::HashSet<_>>();
+            assert_eq!(
+                count(&formula, Some(&projection)),
+                BigUint::from(projected.len()),
+                "{:?}",
+                clauses
+            );
+        }
+    }
+}
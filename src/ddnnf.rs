@@ -0,0 +1,422 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use num_bigint::BigUint;
+
+use crate::{
+    count::{components, condition, normalize, Component},
+    Clauses, Lit, Var,
+};
+
+/// A node of a decision-DNNF circuit, children are referred to by their index.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DdnnfNode {
+    True,
+    False,
+    Lit(Lit),
+    // the children share no vars
+    And(Vec<usize>),
+    // the children disagree on the decision var, so they share no models
+    Or(Var, Vec<usize>),
+}
+
+/// A decision-DNNF circuit compiled from a formula.
+///
+/// Once compiled, counting, weighted counting and conditioning take time linear in the size of the
+/// circuit.
+#[derive(Debug, Clone)]
+pub struct Ddnnf {
+    // the children of a node always come before it
+    pub(crate) nodes: Vec<DdnnfNode>,
+    pub(crate) root: usize,
+    // the vars the models are counted over
+    pub(crate) vars: BTreeSet<Var>,
+}
+
+/// Compiles clauses into a decision-DNNF circuit over all the vars up to the largest one.
+///
+/// The circuit is the trace of a DPLL search on components: a guess becomes a decision node, unit
+/// lits and independent components become the children of an and node, and a component that was
+/// already compiled is shared through a cache.
+pub fn compile(clauses: &Clauses) -> Ddnnf {
+    let formula = clauses
+        .0
+        .iter()
+        .map(|clause| clause.inner().to_vec())
+        .collect();
+    let mut compiler = Compiler {
+        nodes: vec![DdnnfNode::True, DdnnfNode::False],
+        unique: HashMap::new(),
+        cache: HashMap::new(),
+    };
+    let formula = normalize(formula);
+    let root = if formula.iter().any(|clause| clause.is_empty()) {
+        FALSE
+    } else {
+        compiler.compile(formula)
+    };
+    Ddnnf {
+        nodes: compiler.nodes,
+        root,
+        vars: (0..clauses.2).map(Var::from_index).collect(),
+    }
+}
+
+const TRUE: usize = 0;
+const FALSE: usize = 1;
+
+struct Compiler {
+    nodes: Vec<DdnnfNode>,
+    // the index of every node, so that equal nodes are shared
+    unique: HashMap<DdnnfNode, usize>,
+    cache: HashMap<Component, usize>,
+}
+
+impl Compiler {
+    fn node(&mut self, node: DdnnfNode) -> usize {
+        if let Some(&index) = self.unique.get(&node) {
+            return index;
+        }
+        self.nodes.push(node.clone());
+        self.unique.insert(node, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn and(&mut self, children: Vec<usize>) -> usize {
+        if children.contains(&FALSE) {
+            return FALSE;
+        }
+        let mut children = children
+            .into_iter()
+            .filter(|&child| child != TRUE)
+            .collect::<Vec<_>>();
+        match children.len() {
+            0 => TRUE,
+            1 => children[0],
+            _ => {
+                children.sort();
+                self.node(DdnnfNode::And(children))
+            }
+        }
+    }
+
+    fn compile(&mut self, mut clauses: Vec<Vec<Lit>>) -> usize {
+        // unit propagation
+        let mut children = vec![];
+        while let Some(lit) = clauses
+            .iter()
+            .find(|clause| clause.len() == 1)
+            .map(|clause| clause[0])
+        {
+            children.push(self.node(DdnnfNode::Lit(lit)));
+            match condition(&clauses, lit) {
+                Some(res) => clauses = res,
+                None => return FALSE,
+            }
+        }
+        for component in components(clauses) {
+            let child = self.compile_component(component);
+            if child == FALSE {
+                return FALSE;
+            }
+            children.push(child);
+        }
+        self.and(children)
+    }
+
+    fn compile_component(&mut self, mut component: Component) -> usize {
+        for clause in component.iter_mut() {
+            clause.sort();
+        }
+        component.sort();
+        if let Some(&node) = self.cache.get(&component) {
+            return node;
+        }
+
+        // guess the var that occurs the most
+        let mut occurs = HashMap::<Var, usize>::new();
+        for lit in component.iter().flatten() {
+            *occurs.entry(lit.var()).or_default() += 1;
+        }
+        let (&var, _) = occurs
+            .iter()
+            .max_by_key(|(var, n)| (**n, *var))
+            .expect("a component is not empty");
+        let mut children = vec![];
+        for lit in [var.positive(), var.negative()] {
+            if let Some(clauses) = condition(&component, lit) {
+                let child = self.compile(clauses);
+                let lit = self.node(DdnnfNode::Lit(lit));
+                let child = self.and(vec![lit, child]);
+                if child != FALSE {
+                    children.push(child);
+                }
+            }
+        }
+        let node = match children.len() {
+            0 => FALSE,
+            1 => children[0],
+            _ => self.node(DdnnfNode::Or(var, children)),
+        };
+        self.cache.insert(component, node);
+        node
+    }
+}
+
+impl Ddnnf {
+    pub fn nodes(&self) -> &[DdnnfNode] {
+        &self.nodes
+    }
+
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    // the vars below each node
+    pub(crate) fn node_vars(&self) -> Vec<BTreeSet<Var>> {
+        let mut vars: Vec<BTreeSet<Var>> = Vec::with_capacity(self.nodes.len());
+        for node in self.nodes.iter() {
+            let res = match node {
+                DdnnfNode::True | DdnnfNode::False => BTreeSet::new(),
+                DdnnfNode::Lit(lit) => BTreeSet::from([lit.var()]),
+                DdnnfNode::And(children) | DdnnfNode::Or(_, children) => children
+                    .iter()
+                    .flat_map(|&child| vars[child].iter().cloned())
+                    .collect(),
+            };
+            vars.push(res);
+        }
+        vars
+    }
+
+    // the count of each node, over the vars below it
+    pub(crate) fn node_counts(&self, vars: &[BTreeSet<Var>]) -> Vec<BigUint> {
+        let mut counts: Vec<BigUint> = Vec::with_capacity(self.nodes.len());
+        for (index, node) in self.nodes.iter().enumerate() {
+            let res = match node {
+                DdnnfNode::True | DdnnfNode::Lit(_) => BigUint::from(1u32),
+                DdnnfNode::False => BigUint::from(0u32),
+                DdnnfNode::And(children) => children
+                    .iter()
+                    .map(|&child| counts[child].clone())
+                    .product(),
+                DdnnfNode::Or(_, children) => children
+                    .iter()
+                    .map(|&child| {
+                        let gap = vars[index].len() - vars[child].len();
+                        counts[child].clone() << gap
+                    })
+                    .sum(),
+            };
+            counts.push(res);
+        }
+        counts
+    }
+
+    // the weighted count of each node, over the vars below it
+    pub(crate) fn node_weights(
+        &self,
+        vars: &[BTreeSet<Var>],
+        weight: &dyn Fn(Lit) -> f64,
+    ) -> Vec<f64> {
+        let mut counts: Vec<f64> = Vec::with_capacity(self.nodes.len());
+        for (index, node) in self.nodes.iter().enumerate() {
+            let res = match node {
+                DdnnfNode::True => 1.0,
+                DdnnfNode::False => 0.0,
+                DdnnfNode::Lit(lit) => weight(*lit),
+                DdnnfNode::And(children) => children.iter().map(|&child| counts[child]).product(),
+                DdnnfNode::Or(_, children) => children
+                    .iter()
+                    .map(|&child| {
+                        counts[child] * free_weight(vars[index].difference(&vars[child]), weight)
+                    })
+                    .sum(),
+            };
+            counts.push(res);
+        }
+        counts
+    }
+
+    /// The number of models over the vars of the circuit.
+    ///
+    /// The vars that a child of an or node does not mention are free in that child.
+    pub fn count(&self) -> BigUint {
+        let vars = self.node_vars();
+        let gap = self.vars.len() - vars[self.root].len();
+        self.node_counts(&vars).swap_remove(self.root) << gap
+    }
+
+    /// The sum over all models of the product of the weights of their lits.
+    pub fn weighted_count(&self, weight: impl Fn(Lit) -> f64) -> f64 {
+        let vars = self.node_vars();
+        let free = free_weight(self.vars.difference(&vars[self.root]), &weight);
+        self.node_weights(&vars, &weight)[self.root] * free
+    }
+
+    /// The circuit of the formula where the given lits are true.
+    ///
+    /// The vars of the lits are no longer counted.
+    pub fn condition(&self, lits: &[Lit]) -> Ddnnf {
+        let lits = lits.iter().cloned().collect::<HashSet<_>>();
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                DdnnfNode::Lit(lit) if lits.contains(lit) => DdnnfNode::True,
+                DdnnfNode::Lit(lit) if lits.contains(&!*lit) => DdnnfNode::False,
+                node => node.clone(),
+            })
+            .collect();
+        Ddnnf {
+            nodes,
+            root: self.root,
+            vars: self
+                .vars
+                .iter()
+                .filter(|var| !lits.contains(&var.positive()) && !lits.contains(&var.negative()))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The circuit in the `.nnf` format of c2d and d4.
+    ///
+    /// Only the nodes reachable from the root are written, the root is the last one.
+    pub fn to_nnf(&self) -> String {
+        let mut reachable = vec![false; self.nodes.len()];
+        reachable[self.root] = true;
+        for index in (0..self.nodes.len()).rev() {
+            if !reachable[index] {
+                continue;
+            }
+            if let DdnnfNode::And(children) | DdnnfNode::Or(_, children) = &self.nodes[index] {
+                for &child in children {
+                    reachable[child] = true;
+                }
+            }
+        }
+
+        let mut ids = HashMap::<usize, usize>::new();
+        let mut lines = vec![];
+        let mut n_edge = 0;
+        for (index, node) in self.nodes.iter().enumerate() {
+            if !reachable[index] {
+                continue;
+            }
+            let children = |children: &[usize]| {
+                children
+                    .iter()
+                    .map(|child| ids[child].to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let line = match node {
+                DdnnfNode::True => "A 0".to_string(),
+                DdnnfNode::False => "O 0 0".to_string(),
+                DdnnfNode::Lit(lit) => format!("L {}", lit),
+                DdnnfNode::And(c) => format!("A {} {}", c.len(), children(c)),
+                DdnnfNode::Or(var, c) => format!("O {} {} {}", var, c.len(), children(c)),
+            };
+            if let DdnnfNode::And(c) | DdnnfNode::Or(_, c) = node {
+                n_edge += c.len();
+            }
+            ids.insert(index, lines.len());
+            lines.push(line);
+        }
+
+        let mut res = format!("nnf {} {} {}\n", lines.len(), n_edge, self.vars.len());
+        for line in lines {
+            res.push_str(&line);
+            res.push('\n');
+        }
+        res
+    }
+}
+
+// the weight of vars that are free: each of them takes both values
+pub(crate) fn free_weight<'a>(
+    vars: impl Iterator<Item = &'a Var>,
+    weight: &dyn Fn(Lit) -> f64,
+) -> f64 {
+    vars.map(|&var| weight(var.positive()) + weight(var.negative()))
+        .product()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use num_bigint::BigUint;
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{random_lit, satisfies};
+    use crate::*;
+
+    fn random_clauses(rng: &mut impl Rng, n: i32) -> Vec<Vec<i32>> {
+        let mut clauses = (0..rng.gen_range(1..16))
+            .map(|_| {
+                (0..rng.gen_range(1..4))
+                    .map(|_| random_lit(rng, n))
+                    .collect()
+            })
+            .collect::<Vec<Vec<i32>>>();
+        // all the vars up to n are counted
+        clauses.push(vec![n, -n]);
+        clauses
+    }
+
+    #[test]
+    fn compile_count() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(29);
+        let n = 7;
+        for _ in 0..300 {
+            let clauses = random_clauses(&mut rng, n);
+            let models = (0..1u32 << n)
+                .filter(|&bits| satisfies(&clauses, bits))
+                .collect::<Vec<_>>();
+            let formula = Clauses::from(clauses.as_slice());
+            let ddnnf = compile(&formula);
+            assert_eq!(ddnnf.count(), BigUint::from(models.len()), "{:?}", clauses);
+            assert_eq!(ddnnf.count(), count(&formula, None));
+
+            // weight 1 for the positive lit and 2 for the negative one of each var
+            let weight = |lit: Lit| if lit.is_positive() { 1.0 } else { 2.0 };
+            let expected = models
+                .iter()
+                .map(|bits| 2f64.powi(n - bits.count_ones() as i32))
+                .sum::<f64>();
+            assert!((ddnnf.weighted_count(weight) - expected).abs() < 1e-9);
+
+            let lits = [Lit::from_dimacs(1), Lit::from_dimacs(-2)];
+            let conditioned = models.iter().filter(|&&bits| bits & 0b11 == 0b01).count();
+            assert_eq!(
+                ddnnf.condition(&lits).count(),
+                BigUint::from(conditioned),
+                "{:?}",
+                clauses
+            );
+        }
+    }
+
+    #[test]
+    fn compile_empty_clause() {
+        let clauses = vec![vec![1, 2], vec![]];
+        let ddnnf = compile(&Clauses::from(clauses.as_slice()));
+        assert_eq!(ddnnf.nodes()[ddnnf.root()], DdnnfNode::False);
+        assert_eq!(ddnnf.count(), BigUint::from(0u32));
+    }
+
+    #[test]
+    fn nnf() {
+        let clauses = vec![vec![1, 2], vec![-1, 3]];
+        let ddnnf = compile(&Clauses::from(clauses.as_slice()));
+        let nnf = ddnnf.to_nnf();
+        let mut lines = nnf.lines();
+        let header = lines.next().unwrap().split(' ').collect::<Vec<_>>();
+        assert_eq!(header[0], "nnf");
+        assert_eq!(header[3], "3");
+        let nodes = lines.collect::<Vec<_>>();
+        assert_eq!(nodes.len(), header[1].parse::<usize>().unwrap());
+        assert!(nodes.last().unwrap().starts_with("O 1 2"));
+        assert!(nodes.contains(&"L 3") && nodes.contains(&"L -1"));
+    }
+}
@@ -0,0 +1,273 @@
+use std::{error, fmt};
+
+use crate::{Clauses, Cnf, CnfGraph, Lit, PartialSolution};
+
+/// A formula read from the DIMACS CNF format.
+///
+/// Besides clauses, the `x` lines of the extended format used by CryptoMiniSat are read as xor
+/// constraints: `x1 -2 3 0` stands for `x1 ^ !x2 ^ x3 == true`.
+#[derive(Debug, Clone)]
+pub struct Dimacs {
+    // count of vars declared in the header
+    pub n_var: usize,
+    pub clauses: Clauses,
+    // lits && rhs
+    pub xors: Vec<(Vec<Lit>, bool)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DimacsError {
+    // 1-based line of the input
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DimacsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for DimacsError {}
+
+pub fn parse_dimacs(input: &str) -> Result<Dimacs, DimacsError> {
+    let mut n_var = 0;
+    let mut clauses = Vec::new();
+    let mut xors = Vec::new();
+    // a clause may continue over several lines until its 0
+    let mut clause = Vec::new();
+
+    for (index, line) in input.lines().enumerate() {
+        let error = |message: String| DimacsError {
+            line: index + 1,
+            message,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        }
+        // end marker of the SATLIB benchmarks
+        if line.starts_with('%') {
+            break;
+        }
+        if let Some(header) = line.strip_prefix('p') {
+            let fields = header.split_whitespace().collect::<Vec<_>>();
+            if fields.len() != 3 || fields[0] != "cnf" {
+                return Err(error(format!("invalid header `{}`", line)));
+            }
+            n_var = fields[1]
+                .parse()
+                .map_err(|_| error(format!("invalid var count `{}`", fields[1])))?;
+            continue;
+        }
+
+        let (line, is_xor) = match line.strip_prefix('x') {
+            Some(rest) => (rest, true),
+            None => (line, false),
+        };
+        let mut lits = Vec::new();
+        let mut closed = false;
+        for token in line.split_whitespace() {
+            let lit = token
+                .parse::<i32>()
+                .map_err(|_| error(format!("invalid lit `{}`", token)))?;
+            if closed {
+                return Err(error(format!("lit `{}` after the closing 0", token)));
+            }
+            if lit == 0 {
+                closed = true;
+            } else {
+                lits.push(lit);
+            }
+        }
+
+        if is_xor {
+            if !clause.is_empty() {
+                return Err(error("xor inside an unterminated clause".to_string()));
+            }
+            let lits = lits.iter().map(|&lit| Lit::from_dimacs(lit as isize));
+            xors.push((lits.collect(), true));
+        } else {
+            clause.extend(lits);
+            if closed {
+                clauses.push(std::mem::take(&mut clause));
+            }
+        }
+    }
+    // the 0 of the last clause is optional
+    if !clause.is_empty() {
+        clauses.push(clause);
+    }
+
+    Ok(Dimacs {
+        n_var,
+        clauses: Clauses::from(clauses.as_slice()),
+        xors,
+    })
+}
+
+/// Writes the formula back in the DIMACS CNF format, a xor constraint that is false is written
+/// with its first lit negated. A false xor without lits always holds and is left out.
+impl fmt::Display for Dimacs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let n_var = self.n_var.max(self.clauses.2);
+        writeln!(f, "p cnf {} {}", n_var, self.clauses.0.len())?;
+        for clause in self.clauses.0.iter() {
+            for lit in clause.inner() {
+                write!(f, "{} ", lit.to_dimacs())?;
+            }
+            writeln!(f, "0")?;
+        }
+        for (lits, rhs) in self.xors.iter() {
+            if lits.is_empty() && !rhs {
+                continue;
+            }
+            write!(f, "x")?;
+            for (i, lit) in lits.iter().enumerate() {
+                let lit = if i == 0 && !rhs { !*lit } else { *lit };
+                write!(f, "{} ", lit.to_dimacs())?;
+            }
+            writeln!(f, "0")?;
+        }
+        Ok(())
+    }
+}
+
+// read a model in the output format of SAT solvers: the lits of `v` lines up to a 0,
+// the `s` and `c` lines are skipped and the `v` is optional
+pub fn parse_model(input: &str) -> Result<PartialSolution, DimacsError> {
+    let mut lits = vec![];
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('c') || line.starts_with('s') {
+            continue;
+        }
+        let line = line.strip_prefix('v').unwrap_or(line);
+        for token in line.split_whitespace() {
+            let lit = token.parse::<isize>().map_err(|_| DimacsError {
+                line: index + 1,
+                message: format!("invalid lit `{}`", token),
+            })?;
+            if lit != 0 {
+                lits.push(Lit::from_dimacs(lit));
+            }
+        }
+    }
+    let n_var = lits.iter().map(|lit| lit.index() + 1).max().unwrap_or(0);
+    let mut solution = PartialSolution::new(n_var);
+    for lit in lits {
+        solution.assign_lit(lit);
+    }
+    Ok(solution)
+}
+
+// write the assigned lits of a model as a `v` line
+pub fn write_model(solution: &PartialSolution) -> String {
+    let mut res = "v".to_string();
+    for lit in solution.assigned() {
+        res.push_str(&format!(" {}", lit.to_dimacs()));
+    }
+    res.push_str(" 0\n");
+    res
+}
+
+impl From<Dimacs> for Cnf {
+    fn from(value: Dimacs) -> Self {
+        let mut cnf = Cnf::from(value.clauses);
+        for (lits, rhs) in value.xors {
+            cnf.add_xor(&lits, rhs);
+        }
+        cnf
+    }
+}
+
+impl From<Dimacs> for CnfGraph {
+    fn from(value: Dimacs) -> Self {
+        let mut cnf = CnfGraph::from(value.clauses);
+        for (lits, rhs) in value.xors {
+            cnf.add_xor(&lits, rhs);
+        }
+        cnf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::*;
+
+    const INPUT: &str = "c parity of 1, 2, 3 and 3, 4
+p cnf 4 2
+1 2
+  -3 0
+-1 -2 0
+x1 2 3 0
+x-3 4 0
+";
+
+    #[test]
+    fn parse() {
+        let dimacs = parse_dimacs(INPUT).unwrap();
+        assert_eq!(dimacs.n_var, 4);
+        assert_eq!(dimacs.clauses.0.len(), 2);
+        assert_eq!(
+            dimacs.clauses.0[0].inner(),
+            &[1, 2, -3].map(Lit::from_dimacs)
+        );
+        assert_eq!(dimacs.xors.len(), 2);
+        assert_eq!(
+            dimacs.xors[1],
+            ([-3, 4].map(Lit::from_dimacs).to_vec(), true)
+        );
+    }
+
+    #[test]
+    fn parse_error() {
+        let err = parse_dimacs("p cnf 2 1\n1 a 0\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(parse_dimacs("p dnf 2 1\n").is_err());
+    }
+
+    #[test]
+    fn write() {
+        let mut dimacs = parse_dimacs(INPUT).unwrap();
+        dimacs.xors[0].1 = false;
+        let written = dimacs.to_string();
+        assert!(written.starts_with("p cnf 4 2\n1 2 -3 0\n"));
+        assert!(written.contains("x-1 2 3 0\n"));
+        let parsed = parse_dimacs(&written).unwrap();
+        assert_eq!(parsed.xors[1], dimacs.xors[1]);
+
+        // an empty xor is written only when it is a contradiction
+        dimacs.xors = vec![(vec![], false)];
+        let parsed = parse_dimacs(&dimacs.to_string()).unwrap();
+        assert!(parsed.xors.is_empty());
+        dimacs.xors = vec![(vec![], true)];
+        let parsed = parse_dimacs(&dimacs.to_string()).unwrap();
+        assert_eq!(parsed.xors, dimacs.xors);
+
+        let model = parse_model("c comment\ns SATISFIABLE\nv 1 -2\nv 3 0\n").unwrap();
+        assert_eq!(model.true_lits(), vec![0, 2]);
+        assert_eq!(write_model(&model), "v 1 -2 3 0\n");
+        assert_eq!(parse_model("v 1 x 0").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn solve_xors() {
+        let check = |true_lits: Vec<usize>| {
+            let value = |var: usize| true_lits.contains(&(var - 1));
+            assert!(value(1) || value(2) || !value(3));
+            assert!(!value(1) || !value(2));
+            assert!(value(1) ^ value(2) ^ value(3));
+            assert!(!value(3) ^ value(4));
+        };
+
+        let mut cnf = Cnf::from(parse_dimacs(INPUT).unwrap());
+        let (solution, _) = dpll(&mut cnf).unwrap();
+        check(solution.true_lits());
+
+        let mut cnf = CnfGraph::from(parse_dimacs(INPUT).unwrap());
+        let (solution, _) = cfcl(&mut cnf).unwrap();
+        check(solution.true_lits());
+    }
+}
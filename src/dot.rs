@@ -0,0 +1,163 @@
+use std::{collections::BTreeSet, fmt::Write};
+
+use petgraph::visit::EdgeRef;
+
+use crate::{CnfGraph, Lit};
+
+// the node of lit in the DOT graph
+fn node(lit: Lit) -> String {
+    format!("n{}", lit.code())
+}
+
+impl CnfGraph {
+    /// The implication graph in Graphviz DOT, while the clause of clause_id is in conflict.
+    ///
+    /// Every assigned lit is a node labeled with its decision level and the id of its reason
+    /// clause, every edge is labeled with the id of the clause that implied its target. The
+    /// decisions are blue boxes and the conflict is red. Above level 0 the uip is gold, the edges
+    /// of the first uip cut are red and bold, and the lits that the uip implies up to the conflict
+    /// are grouped as the conflict side.
+    pub fn implication_dot(&self, clause_id: usize) -> String {
+        let (learned, resolved) = self.analyze(clause_id).unwrap_or_default();
+        let uip = learned.first().map(|&lit| !lit);
+        let conflict_side = resolved.into_iter().collect::<BTreeSet<_>>();
+
+        let mut dot = String::new();
+        writeln!(dot, "digraph implications {{").unwrap();
+        writeln!(dot, "    rankdir=LR;").unwrap();
+        for &lit in &self.trail {
+            let assignment = self.assignments[&lit.var()];
+            let mut attributes = vec![];
+            let reason = match assignment.reason {
+                Some(reason) => format!("c{}", reason),
+                None => {
+                    attributes.push("shape=box");
+                    "decision".to_string()
+                }
+            };
+            if Some(lit) == uip {
+                attributes.push("peripheries=2, style=filled, fillcolor=gold");
+            } else if assignment.reason.is_none() {
+                attributes.push("style=filled, fillcolor=lightblue");
+            }
+            writeln!(
+                dot,
+                "    {} [label=\"{} @{}\\n{}\"{}{}];",
+                node(lit),
+                lit,
+                assignment.level,
+                reason,
+                if attributes.is_empty() { "" } else { ", " },
+                attributes.join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(
+            dot,
+            "    conflict [label=\"conflict\\nc{}\", shape=octagon, style=filled, \
+             fillcolor=red, fontcolor=white];",
+            clause_id
+        )
+        .unwrap();
+        if uip.is_some() {
+            writeln!(
+                dot,
+                "    subgraph cluster_conflict {{ label=\"conflict side\"; style=dashed; \
+                 color=red; {}conflict; }}",
+                conflict_side
+                    .iter()
+                    .map(|&lit| format!("{}; ", node(lit)))
+                    .collect::<String>()
+            )
+            .unwrap();
+        }
+
+        // the edges that cross the cut go from the other side into the conflict side
+        let mut edge = |from: Lit, to: String, clause_id: usize, to_conflict_side: bool| {
+            let cut = uip.is_some() && to_conflict_side && !conflict_side.contains(&from);
+            writeln!(
+                dot,
+                "    {} -> {} [label=\"c{}\"{}];",
+                node(from),
+                to,
+                clause_id,
+                if cut { ", color=red, penwidth=2" } else { "" }
+            )
+            .unwrap();
+        };
+        for reference in self.graph.edge_references() {
+            let (from, to) = (
+                self.graph[reference.source()],
+                self.graph[reference.target()],
+            );
+            edge(
+                from,
+                node(to),
+                *reference.weight(),
+                conflict_side.contains(&to),
+            );
+        }
+        for &lit in self.clauses[&clause_id].0.all() {
+            edge(!lit, "conflict".to_string(), clause_id, true);
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::*;
+
+    #[test]
+    fn dot_cut() {
+        let clauses = vec![
+            vec![-2, -3, -4, 5],
+            vec![-1, -5, 6],
+            vec![-5, 7],
+            vec![-1, -6, -7],
+            vec![-1, -2, 5],
+            vec![-1, -3, 5],
+            vec![-1, -4, 5],
+            vec![1, 4],
+            vec![-1, 2, 3, 4, 5, -6],
+        ];
+        // the clause learned from the first conflict
+        struct First(Option<Clause>);
+        impl SearchObserver for First {
+            fn on_learned(&mut self, clause: &Clause) {
+                self.0.get_or_insert(clause.clone());
+            }
+        }
+
+        let mut cnf = CnfGraph::from(Clauses::from(clauses.as_slice()));
+        cnf.dot_conflict = Some(1);
+        let mut first = First(None);
+        assert!(cfcl_observed(&mut cnf, &Budget::default(), &mut first).is_sat());
+        let dot = cnf.dot.clone().unwrap();
+        assert!(dot.starts_with("digraph implications {\n") && dot.ends_with("}\n"));
+        assert!(dot.contains("fillcolor=gold") && dot.contains("cluster_conflict"));
+
+        // the lits on the other side of the cut are the negations of the learned clause
+        let mut cut = dot
+            .lines()
+            .filter(|line| line.contains("penwidth"))
+            .map(|line| {
+                let code = line.trim().split(' ').next().unwrap()[1..].parse().unwrap();
+                (!Lit::from_code(code)).to_dimacs()
+            })
+            .collect::<Vec<_>>();
+        cut.sort();
+        cut.dedup();
+        let mut learned = first
+            .0
+            .unwrap()
+            .inner()
+            .iter()
+            .map(|lit| lit.to_dimacs())
+            .collect::<Vec<_>>();
+        learned.sort();
+        assert_eq!(cut, learned);
+    }
+}
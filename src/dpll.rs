@@ -1,6 +1,9 @@
-use std::{collections::HashSet, ops::Not};
+use std::{collections::HashSet, ops::Not, time::Instant};
 
-use crate::{Cnf, Lit};
+use crate::{
+    budget::{memory, Usage},
+    Budget, Clause, Cnf, Lit, NoObserver, SearchObserver, SolveResult, Var,
+};
 
 #[derive(Debug, Clone)]
 pub struct PartialSolution {
@@ -25,6 +28,28 @@ impl PartialSolution {
         self.un_solved.is_empty()
     }
 
+    // count of vars, assigned or not
+    pub fn len(&self) -> usize {
+        self.solution.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.solution.is_empty()
+    }
+
+    pub fn value(&self, var: Var) -> Option<bool> {
+        self.solution.get(var.index()).cloned().flatten()
+    }
+
+    // the true lit of every assigned var
+    pub fn assigned(&self) -> Vec<Lit> {
+        self.solution
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.map(|v| Lit::from_index(i, v)))
+            .collect()
+    }
+
     pub fn false_lits(&self) -> Vec<usize> {
         self.lits(false)
     }
@@ -45,74 +70,204 @@ impl PartialSolution {
 
 pub fn dpll(cnf: &mut Cnf) -> Result<(PartialSolution, &mut Cnf), usize> {
     let mut solution = PartialSolution::new(cnf.n_lit);
-    _dpll(cnf, &mut solution).map(|res| (res, cnf))
+    let mut usage = Usage::new(cnf.progress);
+    let mut search = Search {
+        budget: &Budget::default(),
+        usage: &mut usage,
+        observer: &mut NoObserver,
+        root: None,
+    };
+    let res = _dpll(cnf, &mut solution, &mut search, 0);
+    cnf.stats = usage.finish();
+    res.map(|res| (res, cnf))
 }
 
-fn _dpll(cnf: &mut Cnf, solution: &mut PartialSolution) -> Result<PartialSolution, usize> {
+// solve cnf until a limit of budget is reached or it is interrupted,
+// then cnf is back in the state it was given in and can be solved again
+pub fn dpll_limited(cnf: &mut Cnf, budget: &Budget) -> SolveResult {
+    dpll_observed(cnf, budget, &mut NoObserver)
+}
+
+// solve cnf as dpll_limited does, every event of the search is reported to observer
+pub fn dpll_observed<O: SearchObserver>(
+    cnf: &mut Cnf,
+    budget: &Budget,
+    observer: &mut O,
+) -> SolveResult {
+    let root = cnf.clone();
+    let mut solution = PartialSolution::new(cnf.n_lit);
+    let mut usage = Usage::new(cnf.progress);
+    let mut search = Search {
+        budget,
+        usage: &mut usage,
+        observer,
+        root: Some(&root),
+    };
+    let res = _dpll(cnf, &mut solution, &mut search, 0);
+    if let Ok(solution) = &res {
+        search.observer.on_solution(solution);
+    }
+    let res = usage.result(res);
+    if res.is_unknown() {
+        cnf.restore(root);
+    }
+    cnf.stats = usage.finish();
+    res
+}
+
+// solve cnf with the assumption lits set to true, cnf itself is not changed
+// the same cnf can be solved again under other assumptions
+// return Err: no model satisfies the assumptions
+pub fn dpll_assuming(cnf: &Cnf, assumptions: &[Lit]) -> Result<PartialSolution, usize> {
+    let mut cnf = cnf.clone();
+    let n_lit = assumptions
+        .iter()
+        .map(|lit| lit.index() + 1)
+        .fold(cnf.n_lit, usize::max);
+    let mut solution = PartialSolution::new(n_lit);
+    for &lit in assumptions {
+        match solution.value(lit.var()) {
+            Some(value) if value != lit.is_positive() => return Err(usize::MAX),
+            Some(_) => continue,
+            None => {}
+        }
+        solution.assign_lit(lit);
+        cnf.propagation(lit)?;
+    }
+    let mut usage = Usage::new(None);
+    let mut search = Search {
+        budget: &Budget::default(),
+        usage: &mut usage,
+        observer: &mut NoObserver,
+        root: None,
+    };
+    _dpll(&mut cnf, &mut solution, &mut search, 0)
+}
+
+// what every level of a search shares
+struct Search<'a, O: SearchObserver> {
+    budget: &'a Budget,
+    usage: &'a mut Usage,
+    observer: &'a mut O,
+    // the cnf the search started from, for the lits of the conflict clauses, None when they are
+    // not observed
+    root: Option<&'a Cnf>,
+}
+
+impl<O: SearchObserver> Search<'_, O> {
+    fn conflict(&mut self, clause_id: usize) {
+        self.usage.conflict();
+        let root = self.root;
+        self.observer.on_conflict(clause_id, &|| {
+            let lits = root.and_then(|root| root.clauses.get(&clause_id));
+            Clause(lits.map_or(vec![], |lits| lits.iter().cloned().collect()))
+        });
+    }
+}
+
+// every clause is satisfied, the remaining xor constraints are solved directly
+fn complete(cnf: &Cnf, solution: &mut PartialSolution) -> PartialSolution {
+    for lit in cnf.xors.model() {
+        solution.assign_lit(lit);
+    }
+    solution.clone()
+}
+
+// depth: the count of guesses whose state is saved to try the other value
+// return Err: a conflict, or a limit of budget is reached when usage is stopped
+fn _dpll<O: SearchObserver>(
+    cnf: &mut Cnf,
+    solution: &mut PartialSolution,
+    search: &mut Search<O>,
+    depth: usize,
+) -> Result<PartialSolution, usize> {
     if cnf.clauses.is_empty() {
-        return Ok(solution.clone());
+        return Ok(complete(cnf, solution));
     }
 
     // 1. try  unit propagation
-    let unit_lits = cnf.unit_propagations()?;
+    let start = Instant::now();
+    let unit_lits = cnf.unit_propagations_until(search.budget.terminate.as_ref(), search.observer);
+    search.usage.stats.time.propagation += start.elapsed();
+    let unit_lits = unit_lits.inspect_err(|&clause_id| search.conflict(clause_id))?;
+    search.usage.stats.propagations += unit_lits.len();
     for &lit in &unit_lits {
         solution.assign_lit(lit);
     }
 
     // 2. try pure literal elimination
+    // a lit is not pure when its var also occurs in a xor constraint
     let mut pure = vec![];
     for lit in cnf.occurrences.keys() {
-        if cnf.occurrences.get(&lit.not()).is_none() {
+        if !cnf.occurrences.contains_key(&lit.not()) && !cnf.xors.contains(lit.var()) {
             pure.push(*lit);
         }
     }
     for &lit in &pure {
         solution.assign_lit(lit);
-        cnf.propagation(lit)?;
+        search.observer.on_propagation(lit, None);
+        cnf.propagation(lit)
+            .inspect_err(|&clause_id| search.conflict(clause_id))?;
     }
 
     if cnf.occurrences.is_empty() {
         if cnf.num_clause() == 0 {
-            return Ok(solution.clone());
+            return Ok(complete(cnf, solution));
         } else {
             // conflict
+            search.conflict(usize::MAX);
             return Err(usize::MAX);
         }
     }
 
     // 3. now that we must make a guess
+    let lits = cnf.clauses.values().map(|clause| clause.len()).sum();
+    search.usage.set_memory(memory(lits, depth + 1));
+    if search.usage.exhausted(search.budget) {
+        return Err(usize::MAX);
+    }
     let guess_lit = match cnf.next_guess(crate::Strategy::Direct) {
         Some(lit) => lit,
         None => return Err(usize::MAX),
     };
+    search.usage.stats.decisions += 1;
+    search.usage.stats.max_level = search.usage.stats.max_level.max(depth + 1);
+    search.observer.on_decision(guess_lit, depth + 1);
 
+    cnf.decisions.push(guess_lit);
     let mut _cnf = cnf.clone();
     let mut _solution = solution.clone();
 
     solution.assign_lit(guess_lit);
-    cnf.propagation(guess_lit)?;
-    if cnf.clauses.is_empty() && cnf.occurrences.is_empty() {
-        return Ok(solution.clone());
-    }
-
     // 3.1. try lit is true
-    return match _dpll(cnf, solution) {
+    match cnf
+        .propagation(guess_lit)
+        .inspect_err(|&clause_id| search.conflict(clause_id))
+        .and_then(|_| _dpll(cnf, solution, search, depth + 1))
+    {
         Ok(solution) => Ok(solution),
-        Err(_clause_id) => {
+        Err(clause_id) => {
+            if search.usage.exhausted(search.budget) {
+                return Err(clause_id);
+            }
             // 3.2. try lit is false
-            *cnf = _cnf;
+            cnf.restore(_cnf);
             *solution = _solution;
+            search.observer.on_backtrack(depth);
             let guess_not = guess_lit.not();
-            cnf.propagation(guess_not)?;
+            search.observer.on_propagation(guess_not, None);
+            cnf.propagation(guess_not)
+                .inspect_err(|&clause_id| search.conflict(clause_id))?;
             solution.assign_lit(guess_not);
-            _dpll(cnf, solution)
+            _dpll(cnf, solution, search, depth)
         }
-    };
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use crate::test_util::random_3sat;
     use crate::*;
 
     #[test]
@@ -153,4 +308,25 @@ mod tests {
         println!("{:?}", solution.true_lits());
         println!("{:?}", solution.false_lits());
     }
+
+    // the decisions of a seeded search, on a new cnf every time
+    fn decisions(clauses: &[Vec<i32>], seed: u64) -> Vec<Lit> {
+        let mut cnf = Cnf::from(Clauses::from(clauses));
+        cnf.set_seed(seed);
+        let _ = dpll(&mut cnf);
+        cnf.decisions
+    }
+
+    #[test]
+    fn test_seed() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(44);
+        let n = 30;
+        let clauses = random_3sat(&mut rng, n, 128);
+        let first = decisions(&clauses, 1);
+        assert!(!first.is_empty());
+        assert_eq!(decisions(&clauses, 1), first);
+        assert_ne!(decisions(&clauses, 2), first);
+    }
 }
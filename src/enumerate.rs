@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+
+use crate::{dpll, Cnf, PartialSolution, Var};
+
+/// Iterator over the models of a `Cnf`, created by `models`.
+#[derive(Debug, Clone)]
+pub struct Models {
+    // the branches that are not explored yet, the last one comes first
+    stack: Vec<(Cnf, PartialSolution)>,
+    projection: Option<HashSet<Var>>,
+}
+
+/// Enumerates all the models of a cnf.
+///
+/// The search branches on a var and explores both of its values, so every model is found exactly
+/// once and no blocking clauses are needed. A branch is reported as soon as every clause and xor
+/// constraint is satisfied, the vars it leaves unassigned are don't-cares: a solution with `k`
+/// unassigned vars is a cube that stands for `2^k` models.
+///
+/// With a projection only the given vars are branched on and reported. A branch that leaves none
+/// of them in the formula is reported once when the rest of the formula is satisfiable, so models
+/// that only differ on the other vars count once.
+pub fn models(cnf: &Cnf, projection: Option<&[Var]>) -> Models {
+    Models {
+        stack: vec![(cnf.clone(), PartialSolution::new(cnf.n_lit))],
+        projection: projection.map(|vars| vars.iter().cloned().collect()),
+    }
+}
+
+impl Models {
+    // the smallest var that is left in the formula and in the projection
+    fn next_var(&self, cnf: &Cnf) -> Option<Var> {
+        cnf.occurrences
+            .iter()
+            .filter(|(_, occurs)| !occurs.is_empty())
+            .map(|(lit, _)| lit.var())
+            .chain(cnf.xors.vars())
+            .filter(|var| match &self.projection {
+                Some(projection) => projection.contains(var),
+                None => true,
+            })
+            .min()
+    }
+
+    fn cube(&self, solution: &PartialSolution) -> PartialSolution {
+        match &self.projection {
+            Some(projection) => {
+                let mut cube = PartialSolution::new(solution.len());
+                for lit in solution.assigned() {
+                    if projection.contains(&lit.var()) {
+                        cube.assign_lit(lit);
+                    }
+                }
+                cube
+            }
+            None => solution.clone(),
+        }
+    }
+}
+
+impl Iterator for Models {
+    type Item = PartialSolution;
+
+    fn next(&mut self) -> Option<PartialSolution> {
+        while let Some((mut cnf, mut solution)) = self.stack.pop() {
+            match cnf.unit_propagations() {
+                Ok(lits) => {
+                    for lit in lits {
+                        solution.assign_lit(lit);
+                    }
+                }
+                Err(_) => continue,
+            }
+            if cnf.clauses.is_empty() && cnf.xors.is_empty() {
+                return Some(self.cube(&solution));
+            }
+
+            let var = match self.next_var(&cnf) {
+                Some(var) => var,
+                None => {
+                    // the projection is fully decided, one model of the rest is enough
+                    // without a projection only empty clauses can be left
+                    if self.projection.is_some() && dpll(&mut cnf).is_ok() {
+                        return Some(self.cube(&solution));
+                    }
+                    continue;
+                }
+            };
+            for lit in [var.negative(), var.positive()] {
+                let mut cnf = cnf.clone();
+                let mut solution = solution.clone();
+                solution.assign_lit(lit);
+                if cnf.propagation(lit).is_ok() {
+                    self.stack.push((cnf, solution));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{random_lit, satisfies};
+    use crate::*;
+
+    fn random_clauses(rng: &mut impl Rng, n: i32) -> Vec<Vec<i32>> {
+        (0..rng.gen_range(2..10))
+            .map(|_| {
+                (0..rng.gen_range(1..4))
+                    .map(|_| random_lit(rng, n))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn enumerate() {
+        let clauses = vec![vec![1, 2], vec![-1, -2], vec![3, -2]];
+        let cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+        let cubes = models(&cnf, None).collect::<Vec<_>>();
+        // x1 && !x2 with x3 free, and !x1 && x2 && x3
+        assert_eq!(cubes.len(), 2);
+        let count = cubes
+            .iter()
+            .map(|cube| 1 << (3 - cube.assigned().len()))
+            .sum::<usize>();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn enumerate_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(27);
+        let n = 5;
+        for _ in 0..200 {
+            let clauses = random_clauses(&mut rng, n);
+            let cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+            let expected = (0..1u32 << n)
+                .filter(|&bits| satisfies(&clauses, bits))
+                .count();
+
+            let mut found = 0;
+            for bits in 0..1u32 << n {
+                let matching = models(&cnf, None)
+                    .filter(|cube| {
+                        cube.assigned()
+                            .iter()
+                            .all(|lit| (bits >> lit.index() & 1 == 1) == lit.is_positive())
+                    })
+                    .count();
+                // the cubes are disjoint and only contain models
+                assert!(matching <= 1, "{:?}", clauses);
+                if matching == 1 {
+                    assert!(satisfies(&clauses, bits), "{:?}", clauses);
+                    found += 1;
+                }
+            }
+            assert_eq!(found, expected, "{:?}", clauses);
+        }
+    }
+
+    #[test]
+    fn enumerate_projected() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(28);
+        let n = 5;
+        let projection = [Var::from_dimacs(1), Var::from_dimacs(2)];
+        for _ in 0..200 {
+            let clauses = random_clauses(&mut rng, n);
+            let cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+            let expected = (0..1u32 << n)
+                .filter(|&bits| satisfies(&clauses, bits))
+                .map(|bits| bits & 0b11)
+                .collect::<std::collections::HashSet<_>>();
+
+            let cubes = models(&cnf, Some(&projection)).collect::<Vec<_>>();
+            let count = cubes
+                .iter()
+                .map(|cube| 1 << (2 - cube.assigned().len()))
+                .sum::<usize>();
+            assert_eq!(count, expected.len(), "{:?}", clauses);
+            for cube in cubes {
+                assert!(cube.assigned().iter().all(|lit| lit.index() < 2));
+            }
+        }
+    }
+
+    #[test]
+    fn enumerate_xors() {
+        // x1 ^ x2 ^ x3 with x1 || x2: 3 of the 4 odd assignments
+        let clauses = vec![vec![1, 2]];
+        let mut cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+        cnf.add_xor(&[1, 2, 3].map(Lit::from_dimacs), true);
+        let cubes = models(&cnf, None).collect::<Vec<_>>();
+        assert_eq!(cubes.len(), 3);
+        for cube in cubes {
+            assert!(cube.is_solved());
+            let odd = cube.assigned().iter().filter(|l| l.is_positive()).count() % 2 == 1;
+            assert!(odd);
+        }
+    }
+}
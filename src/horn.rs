@@ -0,0 +1,233 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crate::{two_sat, Clause, Clauses, Lit, PartialSolution, Var};
+
+impl Clauses {
+    // every clause that is not a tautology has at most one positive lit
+    pub fn is_horn(&self) -> bool {
+        self.0.iter().all(|clause| {
+            let lits = clause.inner().iter().collect::<HashSet<_>>();
+            let positive = lits.iter().filter(|lit| lit.is_positive()).count();
+            positive <= 1 || lits.iter().any(|&&lit| lits.contains(&!lit))
+        })
+    }
+}
+
+/// Solves a Horn formula by unit resolution, in linear time.
+///
+/// All vars start false, and a var is made true only when a clause forces it: every negative
+/// lit of the clause is false and its positive lit is not true yet. A clause without positive lit
+/// whose lits all become false makes the formula unsatisfiable. The model is the smallest one,
+/// every var up to the largest one is assigned.
+///
+/// Returns None when clauses have no model.
+pub fn horn(clauses: &Clauses) -> Option<PartialSolution> {
+    assert!(
+        clauses.is_horn(),
+        "horn needs clauses with at most one positive lit"
+    );
+    let n_var = clauses.2;
+    let mut value = vec![false; n_var];
+    // the clauses where each var occurs negated, and the count of negative lits of each
+    // clause that are not false yet
+    let mut negated = vec![vec![]; n_var];
+    let mut count = vec![0; clauses.0.len()];
+    let mut positive = vec![None; clauses.0.len()];
+    let mut queue = vec![];
+    for (id, clause) in clauses.0.iter().enumerate() {
+        let lits = clause.inner().iter().cloned().collect::<BTreeSet<_>>();
+        if lits.iter().any(|lit| lits.contains(&!*lit)) {
+            continue;
+        }
+        for &lit in lits.iter() {
+            if lit.is_positive() {
+                positive[id] = Some(lit.var());
+            } else {
+                negated[lit.index()].push(id);
+                count[id] += 1;
+            }
+        }
+        if count[id] == 0 {
+            queue.push(id);
+        }
+    }
+
+    while let Some(id) = queue.pop() {
+        let var = positive[id]?;
+        if value[var.index()] {
+            continue;
+        }
+        value[var.index()] = true;
+        for &other in negated[var.index()].iter() {
+            count[other] -= 1;
+            if count[other] == 0 {
+                queue.push(other);
+            }
+        }
+    }
+
+    let mut solution = PartialSolution::new(n_var);
+    for (index, value) in value.into_iter().enumerate() {
+        solution.assign_lit(Lit::from_index(index, value));
+    }
+    Some(solution)
+}
+
+/// The vars to flip so that clauses become Horn, None when there are none.
+///
+/// Flipping a var `f` keeps at most one positive lit in a clause when every pair of lits `a`,
+/// `b` of the clause has one lit that is not positive after flipping, which is the 2-SAT clause
+/// `a || b` where a var stands for its flip: the flips are a model of these clauses.
+pub fn horn_renaming(clauses: &Clauses) -> Option<Vec<Var>> {
+    let mut pairs = vec![];
+    for clause in clauses.0.iter() {
+        let lits = clause.inner().iter().cloned().collect::<BTreeSet<_>>();
+        if lits.iter().any(|lit| lits.contains(&!*lit)) {
+            continue;
+        }
+        let lits = lits.into_iter().collect::<Vec<_>>();
+        for (i, &a) in lits.iter().enumerate() {
+            for &b in lits[i + 1..].iter() {
+                pairs.push(Clause(vec![a, b]));
+            }
+        }
+    }
+    let mut pairs = Clauses::from(pairs);
+    pairs.2 = clauses.2;
+    let flips = two_sat(&pairs).ok()?;
+    Some(flips.true_lits().into_iter().map(Var::from_index).collect())
+}
+
+/// Solves clauses that become Horn when the given vars are flipped, see `horn_renaming`.
+pub fn renamed_horn(clauses: &Clauses, flipped: &[Var]) -> Option<PartialSolution> {
+    let flipped = flipped.iter().cloned().collect::<HashSet<_>>();
+    let flip = |lit: Lit| {
+        if flipped.contains(&lit.var()) {
+            !lit
+        } else {
+            lit
+        }
+    };
+    let renamed = clauses
+        .0
+        .iter()
+        .map(|clause| Clause(clause.inner().iter().map(|&lit| flip(lit)).collect()))
+        .collect::<Vec<_>>();
+    let mut renamed = Clauses::from(renamed);
+    renamed.2 = clauses.2;
+
+    let model = horn(&renamed)?;
+    let mut solution = PartialSolution::new(model.len());
+    for lit in model.assigned() {
+        solution.assign_lit(flip(lit));
+    }
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{random_lit, satisfies};
+    use crate::*;
+
+    fn bits(solution: &PartialSolution) -> u32 {
+        solution
+            .true_lits()
+            .iter()
+            .fold(0, |bits, index| bits | 1 << index)
+    }
+
+    // clauses with at most one positive lit, then some vars flipped
+    fn random_horn(rng: &mut impl Rng, n: i32, flips: u32) -> Vec<Vec<i32>> {
+        (0..rng.gen_range(1..16))
+            .map(|_| {
+                let mut clause = (0..rng.gen_range(1..4))
+                    .map(|_| -rng.gen_range(1..=n))
+                    .collect::<Vec<_>>();
+                if rng.gen() {
+                    clause[0] = -clause[0];
+                }
+                clause
+                    .into_iter()
+                    .map(|lit| {
+                        if flips >> (lit.abs() - 1) & 1 == 1 {
+                            -lit
+                        } else {
+                            lit
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn horn_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(40);
+        let n = 6;
+        for _ in 0..300 {
+            let clauses = random_horn(&mut rng, n, 0);
+            let formula = Clauses::from(clauses.as_slice());
+            assert!(formula.is_horn());
+            let models = (0..1u32 << n)
+                .filter(|&bits| satisfies(&clauses, bits))
+                .collect::<Vec<_>>();
+            match horn(&formula) {
+                Some(solution) => {
+                    let found = bits(&solution);
+                    assert!(satisfies(&clauses, found), "{:?}", clauses);
+                    // the smallest model
+                    assert!(models.iter().all(|bits| bits & found == found));
+                }
+                None => assert!(models.is_empty(), "{:?}", clauses),
+            }
+        }
+    }
+
+    #[test]
+    fn horn_renamed_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(40);
+        let n = 6;
+        for round in 0..300 {
+            // renamed Horn formulas, then formulas that may not be renamable
+            let clauses = if round % 2 == 0 {
+                let flips = rng.gen_range(0..1u32 << n);
+                random_horn(&mut rng, n, flips)
+            } else {
+                (0..rng.gen_range(1..8))
+                    .map(|_| {
+                        (0..rng.gen_range(1..4))
+                            .map(|_| random_lit(&mut rng, n))
+                            .collect()
+                    })
+                    .collect()
+            };
+            let formula = Clauses::from(clauses.as_slice());
+            let renamable = (0..1u32 << n).any(|flips| {
+                clauses.iter().all(|clause| {
+                    let positive = clause
+                        .iter()
+                        .filter(|&&lit| (lit > 0) != (flips >> (lit.abs() - 1) & 1 == 1))
+                        .collect::<std::collections::HashSet<_>>();
+                    let tautology = clause.iter().any(|lit| clause.contains(&-lit));
+                    tautology || positive.len() <= 1
+                })
+            });
+            let expected = (0..1u32 << n).any(|bits| satisfies(&clauses, bits));
+
+            match horn_renaming(&formula) {
+                Some(flipped) => {
+                    assert!(renamable, "{:?}", clauses);
+                    match renamed_horn(&formula, &flipped) {
+                        Some(solution) => assert!(satisfies(&clauses, bits(&solution))),
+                        None => assert!(!expected, "{:?}", clauses),
+                    }
+                }
+                None => assert!(!renamable, "{:?}", clauses),
+            }
+            assert_eq!(solve(&formula).is_some(), expected, "{:?}", clauses);
+        }
+    }
+}
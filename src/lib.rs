@@ -1,18 +1,61 @@
+mod approx;
+mod backbone;
+mod blocked;
+mod budget;
 mod cfcl;
 mod clause;
 #[allow(dead_code)]
 mod cnf;
 mod cnf_graph;
+mod count;
+mod ddnnf;
+mod dimacs;
+mod dot;
 mod dpll;
+mod enumerate;
+mod horn;
 #[allow(dead_code)]
 mod lit;
+mod local_search;
+mod observer;
+mod preprocess;
+mod probe;
+mod sample;
+mod solve;
+mod stats;
+mod subsume;
+#[cfg(test)]
+mod test_util;
+mod trace;
+mod two_sat;
+mod vivify;
+mod xor;
 
-pub use cfcl::cfcl;
+pub use approx::{approx_count, ApproxCount};
+pub use backbone::backbone;
+pub use budget::{Budget, Reason, SolveResult, Terminate};
+pub use cfcl::{cfcl, cfcl_limited, cfcl_observed};
 pub use clause::{Clause, Clauses};
 pub use cnf::Cnf;
 pub use cnf_graph::*;
-pub use dpll::{dpll, PartialSolution};
+pub use count::count;
+pub use ddnnf::{compile, Ddnnf, DdnnfNode};
+pub use dimacs::{parse_dimacs, parse_model, write_model, Dimacs, DimacsError};
+pub use dpll::{dpll, dpll_assuming, dpll_limited, dpll_observed, PartialSolution};
+pub use enumerate::{models, Models};
+pub use horn::{horn, horn_renaming, renamed_horn};
 pub use lit::{Lit, Var};
+pub use local_search::{local_search, Break, Heuristic, LocalSearch};
+pub use observer::{NoObserver, SearchObserver};
+pub use preprocess::{Preprocessor, Reconstruction};
+pub use sample::Sampler;
+pub use solve::{solve, solve_parallel};
+pub use stats::{Stats, Times};
+pub use subsume::Subsumption;
+pub use trace::{replay, Divergence, Engine, Event, Trace, TraceWriter};
+pub use two_sat::two_sat;
+pub use vivify::Vivification;
+pub use xor::{XorImplication, XorMatrix};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Strategy {
@@ -0,0 +1,363 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{count::normalize, Clauses, Lit, PartialSolution, Var};
+
+/// How a var of a falsified clause is chosen to be flipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Heuristic {
+    /// A var whose flip falsifies no clause when there is one, otherwise a random var with
+    /// probability `noise` and a var that falsifies the fewest clauses with the rest, 0.567 is a
+    /// good noise for random 3-SAT.
+    WalkSat { noise: f64 },
+    /// A var drawn with a probability that is given by its break count, the count of clauses
+    /// that its flip falsifies.
+    ProbSat(Break),
+}
+
+/// The weight of a var in ProbSAT, it decreases with the break count `b`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Break {
+    /// `(eps + b) ^ -cb`, eps = 0.9 and cb = 2.06 suit random 3-SAT.
+    Polynomial { eps: f64, cb: f64 },
+    /// `cb ^ -b`, cb = 2.5 suits random 3-SAT.
+    Exponential { cb: f64 },
+}
+
+impl Break {
+    fn weight(self, count: usize) -> f64 {
+        match self {
+            Break::Polynomial { eps, cb } => (eps + count as f64).powf(-cb),
+            Break::Exponential { cb } => cb.powf(-(count as f64)),
+        }
+    }
+}
+
+/// Stochastic local search over complete assignments.
+///
+/// Every step flips one var of a falsified clause, until no clause is false or the flips run
+/// out. The count of true lits of each clause, the vars whose flip would falsify a clause (break)
+/// or satisfy one (make) are updated at each flip, so a flip costs the occurrences of its var.
+///
+/// All the random choices come from a generator seeded by the caller, so the same seed gives the
+/// same flips. Local search never proves that a formula has no model.
+#[derive(Debug, Clone)]
+pub struct LocalSearch {
+    pub(crate) clauses: Vec<Vec<Lit>>,
+    // the clauses of each lit code
+    pub(crate) occurrences: Vec<Vec<usize>>,
+    pub(crate) value: Vec<bool>,
+    pub(crate) true_count: Vec<usize>,
+    // the xor of the indexes of the true vars of each clause, the critical var when only one
+    pub(crate) critical: Vec<usize>,
+    pub(crate) breaks: Vec<usize>,
+    pub(crate) makes: Vec<usize>,
+    // the false clauses, and the position of each clause in it
+    pub(crate) falsified: Vec<usize>,
+    pub(crate) position: Vec<usize>,
+    // the assignment with the fewest false clauses so far
+    best: Vec<bool>,
+    best_falsified: usize,
+    empty: bool,
+    pub flips: usize,
+    rng: StdRng,
+}
+
+impl LocalSearch {
+    /// Starts from a random assignment of every var up to the largest one.
+    pub fn new(clauses: &Clauses, seed: u64) -> LocalSearch {
+        let formula = normalize(
+            clauses
+                .0
+                .iter()
+                .map(|clause| clause.inner().to_vec())
+                .collect(),
+        );
+        let n_var = clauses.2;
+        let mut occurrences = vec![vec![]; 2 * n_var];
+        for (id, clause) in formula.iter().enumerate() {
+            for lit in clause {
+                occurrences[lit.code()].push(id);
+            }
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        let value = (0..n_var).map(|_| rng.gen()).collect::<Vec<_>>();
+        let mut search = LocalSearch {
+            empty: formula.iter().any(|clause| clause.is_empty()),
+            true_count: vec![0; formula.len()],
+            critical: vec![0; formula.len()],
+            position: vec![usize::MAX; formula.len()],
+            clauses: formula,
+            occurrences,
+            value: value.clone(),
+            breaks: vec![0; n_var],
+            makes: vec![0; n_var],
+            falsified: vec![],
+            best: value,
+            best_falsified: usize::MAX,
+            flips: 0,
+            rng,
+        };
+        search.reset();
+        search
+    }
+
+    /// Starts again from the given phases, the vars that they leave unassigned keep their value.
+    pub fn set_phases(&mut self, phases: &PartialSolution) {
+        for lit in phases.assigned() {
+            if let Some(value) = self.value.get_mut(lit.index()) {
+                *value = lit.is_positive();
+            }
+        }
+        self.reset();
+    }
+
+    // compute the counts of the current assignment from scratch
+    fn reset(&mut self) {
+        self.breaks.iter_mut().for_each(|count| *count = 0);
+        self.makes.iter_mut().for_each(|count| *count = 0);
+        self.falsified.clear();
+        for id in 0..self.clauses.len() {
+            let (mut count, mut critical) = (0, 0);
+            for lit in self.clauses[id].iter() {
+                if self.is_true(*lit) {
+                    count += 1;
+                    critical ^= lit.index();
+                }
+            }
+            self.true_count[id] = count;
+            self.critical[id] = critical;
+            self.position[id] = usize::MAX;
+            match count {
+                0 => self.falsify(id),
+                1 => self.breaks[critical] += 1,
+                _ => (),
+            }
+        }
+        self.best_falsified = usize::MAX;
+        self.save_best();
+    }
+
+    fn is_true(&self, lit: Lit) -> bool {
+        self.value[lit.index()] == lit.is_positive()
+    }
+
+    fn falsify(&mut self, id: usize) {
+        self.position[id] = self.falsified.len();
+        self.falsified.push(id);
+        for lit in self.clauses[id].iter() {
+            self.makes[lit.index()] += 1;
+        }
+    }
+
+    fn satisfy(&mut self, id: usize) {
+        let position = self.position[id];
+        let last = self.falsified.pop().unwrap();
+        if last != id {
+            self.falsified[position] = last;
+            self.position[last] = position;
+        }
+        self.position[id] = usize::MAX;
+        for lit in self.clauses[id].iter() {
+            self.makes[lit.index()] -= 1;
+        }
+    }
+
+    /// Flips a var and updates the counts of the clauses where it occurs.
+    pub fn flip(&mut self, var: Var) {
+        let index = var.index();
+        self.value[index] = !self.value[index];
+        self.flips += 1;
+        let made = Lit::from_index(index, self.value[index]);
+        for i in 0..self.occurrences[made.code()].len() {
+            let id = self.occurrences[made.code()][i];
+            self.true_count[id] += 1;
+            match self.true_count[id] {
+                1 => {
+                    self.satisfy(id);
+                    self.breaks[index] += 1;
+                }
+                2 => self.breaks[self.critical[id]] -= 1,
+                _ => (),
+            }
+            self.critical[id] ^= index;
+        }
+        for i in 0..self.occurrences[(!made).code()].len() {
+            let id = self.occurrences[(!made).code()][i];
+            self.true_count[id] -= 1;
+            self.critical[id] ^= index;
+            match self.true_count[id] {
+                0 => {
+                    self.breaks[index] -= 1;
+                    self.falsify(id);
+                }
+                1 => self.breaks[self.critical[id]] += 1,
+                _ => (),
+            }
+        }
+        if self.falsified.len() < self.best_falsified {
+            self.save_best();
+        }
+    }
+
+    fn save_best(&mut self) {
+        self.best_falsified = self.falsified.len();
+        self.best.copy_from_slice(&self.value);
+    }
+
+    /// Flips vars of false clauses until none is left, at most `budget` flips.
+    ///
+    /// Returns None when the flips run out or a clause is empty.
+    pub fn solve(&mut self, heuristic: Heuristic, budget: usize) -> Option<PartialSolution> {
+        if self.empty {
+            return None;
+        }
+        for _ in 0..budget {
+            if self.falsified.is_empty() {
+                break;
+            }
+            let id = self.falsified[self.rng.gen_range(0..self.falsified.len())];
+            let var = match heuristic {
+                Heuristic::WalkSat { noise } => self.pick_walksat(id, noise),
+                Heuristic::ProbSat(function) => self.pick_probsat(id, function),
+            };
+            self.flip(var);
+        }
+        if self.falsified.is_empty() {
+            Some(self.best())
+        } else {
+            None
+        }
+    }
+
+    // the var with no break, or a random one with probability noise, or the one with the fewest
+    // breaks and then the most makes
+    fn pick_walksat(&mut self, id: usize, noise: f64) -> Var {
+        let clause = &self.clauses[id];
+        let least = clause
+            .iter()
+            .map(|lit| self.breaks[lit.index()])
+            .min()
+            .unwrap();
+        if least > 0 && self.rng.gen_bool(noise) {
+            return clause.choose(&mut self.rng).unwrap().var();
+        }
+        let best = clause
+            .iter()
+            .filter(|lit| self.breaks[lit.index()] == least)
+            .map(|lit| (self.makes[lit.index()], lit.var()))
+            .max()
+            .unwrap();
+        best.1
+    }
+
+    fn pick_probsat(&mut self, id: usize, function: Break) -> Var {
+        let clause = &self.clauses[id];
+        let weights = clause
+            .iter()
+            .map(|lit| function.weight(self.breaks[lit.index()]))
+            .collect::<Vec<_>>();
+        let mut left = self.rng.gen::<f64>() * weights.iter().sum::<f64>();
+        for (lit, weight) in clause.iter().zip(weights) {
+            if left < weight {
+                return lit.var();
+            }
+            left -= weight;
+        }
+        clause.last().unwrap().var()
+    }
+
+    /// The count of false clauses under the current assignment.
+    pub fn falsified(&self) -> usize {
+        self.falsified.len()
+    }
+
+    /// The assignment with the fewest false clauses found so far, every var is assigned.
+    pub fn best(&self) -> PartialSolution {
+        let mut solution = PartialSolution::new(self.best.len());
+        for (index, &value) in self.best.iter().enumerate() {
+            solution.assign_lit(Lit::from_index(index, value));
+        }
+        solution
+    }
+}
+
+/// Looks for a model of clauses by local search from a random assignment, see `LocalSearch`.
+///
+/// Returns None when no model is found in `budget` flips.
+pub fn local_search(
+    clauses: &Clauses,
+    heuristic: Heuristic,
+    budget: usize,
+    seed: u64,
+) -> Option<PartialSolution> {
+    LocalSearch::new(clauses, seed).solve(heuristic, budget)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{planted, random_3sat, satisfied_by};
+    use crate::*;
+
+    const HEURISTICS: [Heuristic; 3] = [
+        Heuristic::WalkSat { noise: 0.567 },
+        Heuristic::ProbSat(Break::Polynomial { eps: 0.9, cb: 2.06 }),
+        Heuristic::ProbSat(Break::Exponential { cb: 2.5 }),
+    ];
+
+    #[test]
+    fn local_search_counts() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clauses = planted(&mut rng, 20, 85);
+        let formula = Clauses::from(clauses.as_slice());
+        let mut search = LocalSearch::new(&formula, 42);
+        for _ in 0..200 {
+            search.flip(Var::from_index(rng.gen_range(0..20)));
+            let mut expected = search.clone();
+            expected.reset();
+            assert_eq!(search.true_count, expected.true_count);
+            assert_eq!(search.breaks, expected.breaks);
+            assert_eq!(search.makes, expected.makes);
+            let mut falsified = search.falsified.clone();
+            falsified.sort();
+            let mut expected = expected.falsified;
+            expected.sort();
+            assert_eq!(falsified, expected);
+        }
+    }
+
+    #[test]
+    fn local_search_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let n = 10;
+        for round in 0..100 {
+            let m = rng.gen_range(1..45);
+            let clauses = random_3sat(&mut rng, n, m);
+            let formula = Clauses::from(clauses.as_slice());
+            let expected = dpll(&mut Cnf::from(formula.clone())).is_ok();
+            let heuristic = HEURISTICS[round % HEURISTICS.len()];
+            match local_search(&formula, heuristic, 100_000, round as u64) {
+                Some(solution) => assert!(satisfied_by(&clauses, &solution), "{:?}", clauses),
+                None => assert!(!expected, "{:?}", clauses),
+            }
+        }
+    }
+
+    #[test]
+    fn local_search_threshold() {
+        // near the threshold, 4.2 clauses per var
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        for heuristic in HEURISTICS {
+            let clauses = planted(&mut rng, 200, 840);
+            let formula = Clauses::from(clauses.as_slice());
+            let solution = local_search(&formula, heuristic, 1_000_000, 42).unwrap();
+            assert!(satisfied_by(&clauses, &solution));
+
+            // the same seed gives the same model
+            let again = local_search(&formula, heuristic, 1_000_000, 42).unwrap();
+            assert_eq!(solution.assigned(), again.assigned());
+        }
+    }
+}
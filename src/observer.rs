@@ -0,0 +1,162 @@
+use crate::{Clause, Lit, PartialSolution};
+
+/// Callbacks for the events of a search, to build visualizers, loggers or metrics on top of the
+/// engines, see `dpll_observed` and `cfcl_observed`.
+///
+/// Every callback does nothing by default, and the engines are generic over their observer, so a
+/// search with `NoObserver` compiles to the same code as a search without any.
+pub trait SearchObserver {
+    /// A guess, with the decision level it opens.
+    fn on_decision(&mut self, _lit: Lit, _level: usize) {}
+
+    /// A lit implied by the clause of reason, reported before it is propagated further.
+    /// The reason is None for a lit that dpll assigns without a clause: a pure lit, or the other
+    /// value of a guess that failed.
+    fn on_propagation(&mut self, _lit: Lit, _reason: Option<usize>) {}
+
+    /// All the lits of a clause are false, clause builds its lits only when it is called. The
+    /// clause is empty when it is not a clause of the formula: a conflict of the xor constraints,
+    /// or of dpll running out of lits.
+    fn on_conflict(&mut self, _clause_id: usize, _clause: &dyn Fn() -> Clause) {}
+
+    /// A clause learned by cfcl from the last conflict.
+    fn on_learned(&mut self, _clause: &Clause) {}
+
+    /// The search goes back to the state it had at level.
+    fn on_backtrack(&mut self, _level: usize) {}
+
+    /// cfcl starts again from the root, with its learned clauses reduced.
+    fn on_restart(&mut self) {}
+
+    /// The model the search found.
+    fn on_solution(&mut self, _solution: &PartialSolution) {}
+}
+
+/// The observer of a search that nobody observes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoObserver;
+
+impl SearchObserver for NoObserver {}
+
+impl<O: SearchObserver + ?Sized> SearchObserver for &mut O {
+    fn on_decision(&mut self, lit: Lit, level: usize) {
+        (**self).on_decision(lit, level)
+    }
+
+    fn on_propagation(&mut self, lit: Lit, reason: Option<usize>) {
+        (**self).on_propagation(lit, reason)
+    }
+
+    fn on_conflict(&mut self, clause_id: usize, clause: &dyn Fn() -> Clause) {
+        (**self).on_conflict(clause_id, clause)
+    }
+
+    fn on_learned(&mut self, clause: &Clause) {
+        (**self).on_learned(clause)
+    }
+
+    fn on_backtrack(&mut self, level: usize) {
+        (**self).on_backtrack(level)
+    }
+
+    fn on_restart(&mut self) {
+        (**self).on_restart()
+    }
+
+    fn on_solution(&mut self, solution: &PartialSolution) {
+        (**self).on_solution(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::SeedableRng;
+
+    use crate::test_util::random_3sat;
+    use crate::*;
+
+    // the assignment that the events describe, with the count of each event
+    #[derive(Default)]
+    struct Trail {
+        // the lits and the level they are assigned at
+        lits: Vec<(Lit, usize)>,
+        level: usize,
+        decisions: usize,
+        conflicts: usize,
+        learned: usize,
+        restarts: usize,
+        solution: Option<PartialSolution>,
+    }
+
+    impl SearchObserver for Trail {
+        fn on_decision(&mut self, lit: Lit, level: usize) {
+            assert_eq!(level, self.level + 1);
+            self.level = level;
+            self.decisions += 1;
+            self.lits.push((lit, level));
+        }
+
+        fn on_propagation(&mut self, lit: Lit, _reason: Option<usize>) {
+            assert!(self.lits.iter().all(|&(other, _)| other.var() != lit.var()));
+            self.lits.push((lit, self.level));
+        }
+
+        fn on_conflict(&mut self, _clause_id: usize, clause: &dyn Fn() -> Clause) {
+            self.conflicts += 1;
+            for lit in clause().inner() {
+                assert!(self.lits.iter().any(|&(other, _)| other == !*lit));
+            }
+        }
+
+        fn on_learned(&mut self, _clause: &Clause) {
+            self.learned += 1;
+        }
+
+        fn on_backtrack(&mut self, level: usize) {
+            assert!(level < self.level);
+            self.level = level;
+            self.lits.retain(|&(_, other)| other <= level);
+        }
+
+        fn on_restart(&mut self) {
+            assert_eq!(self.level, 0);
+            self.restarts += 1;
+            self.lits.clear();
+        }
+
+        fn on_solution(&mut self, solution: &PartialSolution) {
+            for &(lit, _) in &self.lits {
+                assert_eq!(solution.value(lit.var()), Some(lit.is_positive()));
+            }
+            self.solution = Some(solution.clone());
+        }
+    }
+
+    #[test]
+    fn observer_events() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(48);
+        for _ in 0..8 {
+            let clauses = random_3sat(&mut rng, 60, 255);
+            let clauses = Clauses::from(clauses.as_slice());
+
+            let mut cnf = CnfGraph::from(clauses.clone());
+            let mut trail = Trail::default();
+            let res = cfcl_observed(&mut cnf, &Budget::default(), &mut trail);
+            assert_eq!(trail.decisions, cnf.stats.decisions);
+            assert_eq!(trail.conflicts, cnf.stats.conflicts);
+            assert_eq!(trail.learned, cnf.stats.learned);
+            assert_eq!(trail.restarts, cnf.stats.restarts);
+            assert_eq!(trail.solution.is_some(), res.is_sat());
+
+            let mut cnf = Cnf::from(clauses);
+            let mut trail = Trail::default();
+            let expected = dpll_observed(&mut cnf, &Budget::default(), &mut trail);
+            assert_eq!(trail.decisions, cnf.stats.decisions);
+            assert_eq!(trail.conflicts, cnf.stats.conflicts);
+            assert_eq!(trail.learned, 0);
+            assert_eq!(trail.solution.is_some(), expected.is_sat());
+            assert_eq!(res.is_sat(), expected.is_sat());
+        }
+    }
+}
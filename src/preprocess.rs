@@ -0,0 +1,465 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use crate::{Clause, Clauses, DimacsError, Lit, PartialSolution, Var};
+
+/// The clauses that simplification removed, in order, with the lit that repairs each of them.
+///
+/// A model of the simplified formula is extended to a model of the original one by going through
+/// the removed clauses from the last one to the first: a clause that the model falsifies is
+/// satisfied by making its witness lit true.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconstruction {
+    n_var: usize,
+    stack: Vec<(Lit, Vec<Lit>)>,
+}
+
+impl Reconstruction {
+    pub fn new(n_var: usize) -> Reconstruction {
+        Reconstruction {
+            n_var,
+            stack: vec![],
+        }
+    }
+
+    pub fn push(&mut self, witness: Lit, clause: Vec<Lit>) {
+        debug_assert!(clause.contains(&witness));
+        self.n_var = self.n_var.max(witness.index() + 1);
+        self.stack.push((witness, clause));
+    }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Extends a model of the simplified formula to a model of the original one.
+    ///
+    /// The vars that the model leaves unassigned are taken as false first, so every var of the
+    /// original formula is assigned.
+    pub fn extend(&self, model: &PartialSolution) -> PartialSolution {
+        let mut values = (0..self.n_var.max(model.len()))
+            .map(|index| model.value(Var::from_index(index)).unwrap_or(false))
+            .collect::<Vec<_>>();
+        for (witness, clause) in self.stack.iter().rev() {
+            let satisfied = clause
+                .iter()
+                .any(|lit| values[lit.index()] == lit.is_positive());
+            if !satisfied {
+                values[witness.index()] = witness.is_positive();
+            }
+        }
+        let mut res = PartialSolution::new(values.len());
+        for (index, value) in values.into_iter().enumerate() {
+            res.assign_lit(Lit::from_index(index, value));
+        }
+        res
+    }
+}
+
+/// Written like DIMACS: a `p rec` header with the count of vars and of clauses, then a line per
+/// clause from the first removed one, the witness lit first and the lits of the clause up to a 0.
+impl fmt::Display for Reconstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "p rec {} {}", self.n_var, self.stack.len())?;
+        for (witness, clause) in self.stack.iter() {
+            write!(f, "{}", witness.to_dimacs())?;
+            for lit in clause {
+                write!(f, " {}", lit.to_dimacs())?;
+            }
+            writeln!(f, " 0")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Reconstruction {
+    type Err = DimacsError;
+
+    fn from_str(input: &str) -> Result<Reconstruction, DimacsError> {
+        let mut res = Reconstruction::default();
+        for (index, line) in input.lines().enumerate() {
+            let error = |message: String| DimacsError {
+                line: index + 1,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('c') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('p') {
+                let fields = header.split_whitespace().collect::<Vec<_>>();
+                if fields.len() != 3 || fields[0] != "rec" {
+                    return Err(error(format!("invalid header `{}`", line)));
+                }
+                res.n_var = fields[1]
+                    .parse()
+                    .map_err(|_| error(format!("invalid var count `{}`", fields[1])))?;
+                continue;
+            }
+            let mut lits = vec![];
+            for token in line.split_whitespace() {
+                let lit = token
+                    .parse::<isize>()
+                    .map_err(|_| error(format!("invalid lit `{}`", token)))?;
+                if lit == 0 {
+                    break;
+                }
+                lits.push(Lit::from_dimacs(lit));
+            }
+            match lits.split_first() {
+                Some((&witness, clause)) if clause.contains(&witness) => {
+                    res.push(witness, clause.to_vec())
+                }
+                _ => return Err(error("the witness is not in the clause".to_string())),
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Simplifies clauses before they are solved.
+///
+/// The clauses are kept sorted and without duplicated lits, with an occurrence list per lit.
+/// The passes only remove or shorten clauses or add implied ones, every removed clause that is
+/// not implied by the remaining formula is recorded in the `Reconstruction`, so the simplified
+/// formula is satisfiable exactly when the original one is.
+///
+/// Frozen vars are never removed from the formula: the models of the simplified formula
+/// restricted to the frozen vars are the models of the original formula restricted to them.
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    // a removed clause is None, the ids of the others do not change
+    pub(crate) clauses: Vec<Option<Vec<Lit>>>,
+    // a bit per var of each clause, modulo 64, to rule out most subset tests
+    pub(crate) signatures: Vec<u64>,
+    // ordered, so that the passes are deterministic
+    pub(crate) occurrences: HashMap<Lit, BTreeSet<usize>>,
+    pub(crate) frozen: HashSet<Var>,
+    // the clauses that were added or shortened since they were last used for subsumption
+    pub(crate) queue: Vec<usize>,
+    pub(crate) reconstruction: Reconstruction,
+    // an empty clause was derived
+    pub(crate) unsat: bool,
+    n_var: usize,
+}
+
+impl Preprocessor {
+    pub fn new(clauses: &Clauses) -> Preprocessor {
+        let mut res = Preprocessor {
+            clauses: vec![],
+            signatures: vec![],
+            occurrences: HashMap::new(),
+            frozen: HashSet::new(),
+            queue: vec![],
+            reconstruction: Reconstruction::new(clauses.2),
+            unsat: false,
+            n_var: clauses.2,
+        };
+        for clause in clauses.0.iter() {
+            res.add_clause(clause.inner().to_vec());
+        }
+        res
+    }
+
+    /// Keeps var in the simplified formula.
+    pub fn freeze(&mut self, var: Var) {
+        self.frozen.insert(var);
+    }
+
+    pub fn is_unsat(&self) -> bool {
+        self.unsat
+    }
+
+    pub fn reconstruction(&self) -> &Reconstruction {
+        &self.reconstruction
+    }
+
+    /// The simplified formula, a single empty clause when it is unsatisfiable.
+    pub fn clauses(&self) -> Clauses {
+        let clauses = if self.unsat {
+            vec![Clause(vec![])]
+        } else {
+            self.clauses
+                .iter()
+                .flatten()
+                .map(|clause| Clause(clause.clone()))
+                .collect()
+        };
+        let mut res = Clauses::from(clauses);
+        res.2 = res.2.max(self.n_var);
+        res
+    }
+
+    // add a clause, tautologies are dropped
+    // return the id of the clause
+    pub(crate) fn add_clause(&mut self, lits: Vec<Lit>) -> Option<usize> {
+        let lits = lits.into_iter().collect::<BTreeSet<_>>();
+        if lits.iter().any(|lit| lits.contains(&!*lit)) {
+            return None;
+        }
+        if lits.is_empty() {
+            self.unsat = true;
+        }
+        let id = self.clauses.len();
+        for &lit in lits.iter() {
+            self.occurrences.entry(lit).or_default().insert(id);
+        }
+        let clause = lits.into_iter().collect::<Vec<_>>();
+        self.signatures.push(signature(&clause));
+        self.clauses.push(Some(clause));
+        self.queue.push(id);
+        Some(id)
+    }
+
+    // remove a clause that is implied by the others
+    pub(crate) fn remove_clause(&mut self, id: usize) -> Option<Vec<Lit>> {
+        let clause = self.clauses[id].take()?;
+        for lit in clause.iter() {
+            if let Some(occurs) = self.occurrences.get_mut(lit) {
+                occurs.remove(&id);
+                if occurs.is_empty() {
+                    self.occurrences.remove(lit);
+                }
+            }
+        }
+        Some(clause)
+    }
+
+    // remove a clause that may not be implied, it is repaired by witness in the reconstruction
+    pub(crate) fn eliminate_clause(&mut self, id: usize, witness: Lit) {
+        if let Some(clause) = self.remove_clause(id) {
+            self.reconstruction.push(witness, clause);
+        }
+    }
+
+    // remove lit from a clause, the shorter clause must be implied
+    pub(crate) fn strengthen(&mut self, id: usize, lit: Lit) {
+        if let Some(clause) = self.clauses[id].as_mut() {
+            clause.retain(|&other| other != lit);
+            self.signatures[id] = signature(clause);
+            if clause.is_empty() {
+                self.unsat = true;
+            }
+            if let Some(occurs) = self.occurrences.get_mut(&lit) {
+                occurs.remove(&id);
+                if occurs.is_empty() {
+                    self.occurrences.remove(&lit);
+                }
+            }
+            self.queue.push(id);
+        }
+    }
+
+    pub(crate) fn occurs(&self, lit: Lit) -> Vec<usize> {
+        self.occurrences
+            .get(&lit)
+            .map_or(vec![], |occurs| occurs.iter().cloned().collect())
+    }
+
+    // the vars that are left in the formula, ordered
+    pub(crate) fn vars(&self) -> BTreeSet<Var> {
+        self.occurrences.keys().map(|lit| lit.var()).collect()
+    }
+
+    /// Bounded variable elimination by clause distribution, as in SatELite.
+    ///
+    /// A var is eliminated by replacing the clauses it occurs in with all their non-tautological
+    /// resolvents on it, but only when that does not increase the number of clauses. Subsumption
+    /// and self-subsuming resolution run before and after every elimination. Frozen vars are not
+    /// eliminated.
+    ///
+    /// Returns the number of eliminated vars.
+    pub fn eliminate(&mut self) -> usize {
+        let mut res = 0;
+        self.subsume();
+        loop {
+            // the vars with the fewest resolvents first
+            let mut vars = self
+                .vars()
+                .into_iter()
+                .filter(|var| !self.frozen.contains(var))
+                .map(|var| {
+                    let pos = self.occurs(var.positive()).len();
+                    let neg = self.occurs(var.negative()).len();
+                    (pos * neg, var)
+                })
+                .collect::<Vec<_>>();
+            vars.sort();
+
+            let mut changed = false;
+            for (_, var) in vars {
+                if self.unsat {
+                    return res;
+                }
+                if self.eliminate_var(var) {
+                    res += 1;
+                    changed = true;
+                    self.subsume();
+                }
+            }
+            if !changed {
+                return res;
+            }
+        }
+    }
+
+    // return false: var is not eliminated since the formula would grow
+    fn eliminate_var(&mut self, var: Var) -> bool {
+        let pos = self.occurs(var.positive());
+        let neg = self.occurs(var.negative());
+        if pos.is_empty() && neg.is_empty() {
+            return false;
+        }
+        let mut resolvents = vec![];
+        for &p in pos.iter() {
+            for &n in neg.iter() {
+                let left = self.clauses[p].as_ref().unwrap();
+                let right = self.clauses[n].as_ref().unwrap();
+                if let Some(resolvent) = resolve(left, right, var) {
+                    resolvents.push(resolvent);
+                    if resolvents.len() > pos.len() + neg.len() {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        for id in pos {
+            self.eliminate_clause(id, var.positive());
+        }
+        for id in neg {
+            self.eliminate_clause(id, var.negative());
+        }
+        for resolvent in resolvents {
+            self.add_clause(resolvent);
+        }
+        true
+    }
+}
+
+pub(crate) fn signature(clause: &[Lit]) -> u64 {
+    clause
+        .iter()
+        .fold(0, |res, lit| res | 1 << (lit.index() % 64))
+}
+
+// the resolvent of two clauses on var
+// return None: the resolvent is a tautology
+pub(crate) fn resolve(left: &[Lit], right: &[Lit], var: Var) -> Option<Vec<Lit>> {
+    let mut res = left
+        .iter()
+        .chain(right.iter())
+        .filter(|lit| lit.var() != var)
+        .cloned()
+        .collect::<Vec<_>>();
+    res.sort();
+    res.dedup();
+    if res.windows(2).any(|pair| pair[0] == !pair[1]) {
+        return None;
+    }
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashSet;
+
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{random_lit, satisfied_by, satisfies};
+    use crate::*;
+
+    fn solve(clauses: &Clauses) -> Option<PartialSolution> {
+        let mut cnf = Cnf::from(clauses.clone());
+        dpll(&mut cnf).ok().map(|(solution, _)| solution)
+    }
+
+    #[test]
+    fn eliminate_small() {
+        // 2 is only used to chain 1 to 3
+        let clauses = vec![vec![-1, 2], vec![-2, 3], vec![1, 4], vec![-3, -4]];
+        let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.as_slice()));
+        preprocessor.freeze(Var::from_dimacs(1));
+        assert!(preprocessor.eliminate() > 0);
+        let simplified = preprocessor.clauses();
+        assert!(simplified
+            .0
+            .iter()
+            .flat_map(|clause| clause.inner())
+            .all(|lit| lit.var() == Var::from_dimacs(1)));
+
+        let model = preprocessor
+            .reconstruction()
+            .extend(&solve(&simplified).unwrap());
+        assert!(satisfied_by(&clauses, &model));
+    }
+
+    #[test]
+    fn reconstruction_text() {
+        let mut reconstruction = Reconstruction::new(4);
+        reconstruction.push(Lit::from_dimacs(-2), [1, -2].map(Lit::from_dimacs).to_vec());
+        reconstruction.push(Lit::from_dimacs(3), [3].map(Lit::from_dimacs).to_vec());
+        let text = reconstruction.to_string();
+        assert_eq!(text, "p rec 4 2\n-2 1 -2 0\n3 3 0\n");
+        assert_eq!(text.parse::<Reconstruction>(), Ok(reconstruction));
+        assert!("p rec 4 1\n2 1 0\n".parse::<Reconstruction>().is_err());
+    }
+
+    #[test]
+    fn eliminate_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(33);
+        let n = 7;
+        for _ in 0..300 {
+            let clauses = (0..rng.gen_range(1..20))
+                .map(|_| {
+                    (0..rng.gen_range(1..4))
+                        .map(|_| random_lit(&mut rng, n))
+                        .collect()
+                })
+                .collect::<Vec<Vec<i32>>>();
+            let frozen = [1, 2];
+            let projected = |clauses: &[Vec<i32>]| {
+                (0..1u32 << n)
+                    .filter(|&bits| satisfies(clauses, bits))
+                    .map(|bits| bits & 0b11)
+                    .collect::<HashSet<_>>()
+            };
+
+            let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.as_slice()));
+            for var in frozen {
+                preprocessor.freeze(Var::from_dimacs(var));
+            }
+            preprocessor.eliminate();
+            let simplified = preprocessor.clauses();
+            let simplified_ints = simplified
+                .0
+                .iter()
+                .map(|clause| {
+                    clause
+                        .inner()
+                        .iter()
+                        .map(|lit| lit.to_dimacs() as i32)
+                        .collect()
+                })
+                .collect::<Vec<Vec<i32>>>();
+            // the frozen vars keep all their values
+            assert_eq!(projected(&clauses), projected(&simplified_ints));
+
+            match solve(&simplified) {
+                Some(model) => {
+                    let model = preprocessor.reconstruction().extend(&model);
+                    assert!(satisfied_by(&clauses, &model), "{:?}", clauses);
+                }
+                None => assert!(projected(&clauses).is_empty(), "{:?}", clauses),
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use petgraph::{algo::tarjan_scc, graph::DiGraph};
+
+use crate::{Lit, Preprocessor};
+
+impl Preprocessor {
+    /// Failed literal probing.
+    ///
+    /// Both lits of every var are propagated on their own. When one of them leads to a conflict,
+    /// the other one is added as a unit, and a lit that both of them imply is added as a unit too.
+    ///
+    /// Returns the number of units that were found.
+    pub fn probe(&mut self) -> usize {
+        let mut res = 0;
+        self.subsume();
+        for var in self.vars() {
+            if self.unsat {
+                break;
+            }
+            let known = match self.implied(&[]) {
+                Some(known) => known,
+                None => {
+                    self.add_clause(vec![]);
+                    break;
+                }
+            };
+            if known.contains(&var.positive()) || known.contains(&var.negative()) {
+                continue;
+            }
+            let mut units = match (
+                self.implied(&[var.positive()]),
+                self.implied(&[var.negative()]),
+            ) {
+                (None, None) => {
+                    self.add_clause(vec![]);
+                    break;
+                }
+                (None, Some(_)) => vec![var.negative()],
+                (Some(_), None) => vec![var.positive()],
+                (Some(pos), Some(neg)) => pos
+                    .intersection(&neg)
+                    .filter(|lit| !known.contains(lit))
+                    .cloned()
+                    .collect(),
+            };
+            units.sort();
+            for lit in units {
+                self.add_clause(vec![lit]);
+                res += 1;
+            }
+            self.subsume();
+        }
+        res
+    }
+
+    /// Equivalent literal substitution.
+    ///
+    /// The binary clauses form an implication graph over the lits, the lits of a strongly
+    /// connected component are all equivalent. Every var of a component is replaced by a
+    /// representative lit, a frozen one when there is one, and the reconstruction gives it the
+    /// value of its representative. A component with both lits of a var makes the formula
+    /// unsatisfiable. Frozen vars are not replaced.
+    ///
+    /// Returns the number of vars that were replaced.
+    pub fn substitute_equivalences(&mut self) -> usize {
+        self.subsume();
+        if self.unsat {
+            return 0;
+        }
+        let mut graph = DiGraph::<Lit, ()>::new();
+        let mut nodes = HashMap::new();
+        for clause in self
+            .clauses
+            .iter()
+            .flatten()
+            .filter(|clause| clause.len() == 2)
+        {
+            let mut node = |lit: Lit| *nodes.entry(lit).or_insert_with(|| graph.add_node(lit));
+            let (a, b) = (clause[0], clause[1]);
+            let (not_a, not_b) = (node(!a), node(!b));
+            let (a, b) = (node(a), node(b));
+            graph.add_edge(not_a, b, ());
+            graph.add_edge(not_b, a, ());
+        }
+
+        // the lit equal to each var that is replaced
+        let mut replaced = BTreeMap::new();
+        for component in tarjan_scc(&graph) {
+            let lits = component
+                .iter()
+                .map(|&node| graph[node])
+                .collect::<HashSet<_>>();
+            if lits.iter().any(|lit| lits.contains(&!*lit)) {
+                self.add_clause(vec![]);
+                return 0;
+            }
+            // the complementary component chooses the same var
+            let repr = *lits
+                .iter()
+                .min_by_key(|lit| (!self.frozen.contains(&lit.var()), lit.var()))
+                .unwrap();
+            for &lit in lits.iter() {
+                if lit.var() != repr.var() && !self.frozen.contains(&lit.var()) {
+                    let equal = if lit.is_positive() { repr } else { !repr };
+                    replaced.insert(lit.var(), equal);
+                }
+            }
+        }
+
+        for (&var, &equal) in replaced.iter() {
+            for lit in [var.positive(), var.negative()] {
+                for id in self.occurs(lit) {
+                    let clause = self.remove_clause(id).unwrap();
+                    let clause = clause
+                        .into_iter()
+                        .map(|other| match other {
+                            other if other == var.positive() => equal,
+                            other if other == var.negative() => !equal,
+                            other => other,
+                        })
+                        .collect();
+                    self.add_clause(clause);
+                }
+            }
+            // var is true exactly when equal is
+            self.reconstruction
+                .push(var.positive(), vec![var.positive(), !equal]);
+            self.reconstruction
+                .push(var.negative(), vec![var.negative(), equal]);
+        }
+        self.subsume();
+        replaced.len()
+    }
+
+    // the lits implied by unit propagation from lits and the units of the formula
+    // return None: there is a conflict
+    pub(crate) fn implied(&self, lits: &[Lit]) -> Option<HashSet<Lit>> {
+        let mut assigned = HashSet::new();
+        let mut queue = lits.to_vec();
+        queue.extend(
+            self.clauses
+                .iter()
+                .flatten()
+                .filter(|clause| clause.len() == 1)
+                .map(|clause| clause[0]),
+        );
+        while let Some(lit) = queue.pop() {
+            if assigned.contains(&!lit) {
+                return None;
+            }
+            if !assigned.insert(lit) {
+                continue;
+            }
+            for id in self.occurs(!lit) {
+                let clause = self.clauses[id].as_ref().unwrap();
+                if clause.iter().any(|other| assigned.contains(other)) {
+                    continue;
+                }
+                let mut free = clause.iter().filter(|other| !assigned.contains(&!**other));
+                match (free.next(), free.next()) {
+                    (None, _) => return None,
+                    (Some(&unit), None) => queue.push(unit),
+                    _ => {}
+                }
+            }
+        }
+        Some(assigned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashSet;
+
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{random_lit, satisfies};
+    use crate::*;
+
+    fn to_ints(clauses: &Clauses) -> Vec<Vec<i32>> {
+        clauses
+            .0
+            .iter()
+            .map(|clause| {
+                clause
+                    .inner()
+                    .iter()
+                    .map(|lit| lit.to_dimacs() as i32)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn probe_units() {
+        // !1 fails, and 4 and !4 both imply 6
+        let clauses = vec![
+            vec![1, 2],
+            vec![1, 3],
+            vec![-2, -3],
+            vec![-4, 6],
+            vec![4, 5],
+            vec![-5, 6],
+        ];
+        let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.as_slice()));
+        assert_eq!(preprocessor.probe(), 2);
+        let mut left = to_ints(&preprocessor.clauses());
+        left.sort();
+        assert_eq!(left, vec![vec![-2, -3], vec![1], vec![4, 5], vec![6]]);
+    }
+
+    #[test]
+    fn substitute_small() {
+        // 1 == 2 == !3
+        let clauses = vec![
+            vec![-1, 2],
+            vec![1, -2],
+            vec![2, 3],
+            vec![-2, -3],
+            vec![1, 3, 4],
+        ];
+        let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.as_slice()));
+        assert_eq!(preprocessor.substitute_equivalences(), 2);
+        assert!(to_ints(&preprocessor.clauses()).is_empty());
+        let model = PartialSolution::new(4);
+        let model = preprocessor.reconstruction().extend(&model);
+        assert_eq!(
+            model.value(Var::from_dimacs(1)),
+            model.value(Var::from_dimacs(2))
+        );
+        assert_ne!(
+            model.value(Var::from_dimacs(1)),
+            model.value(Var::from_dimacs(3))
+        );
+
+        // 1 == 2 and 1 == !2
+        let clauses = vec![vec![-1, 2], vec![1, -2], vec![1, 2], vec![-1, -2]];
+        let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.as_slice()));
+        preprocessor.substitute_equivalences();
+        assert!(preprocessor.is_unsat());
+    }
+
+    #[test]
+    fn probe_substitute_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(35);
+        let n = 7;
+        for _ in 0..300 {
+            // mostly binary clauses, so that there are equivalences
+            let clauses = (0..rng.gen_range(1..20))
+                .map(|_| {
+                    (0..rng.gen_range(1..=3).min(rng.gen_range(2..4)))
+                        .map(|_| random_lit(&mut rng, n))
+                        .collect()
+                })
+                .collect::<Vec<Vec<i32>>>();
+            let projected = |clauses: &[Vec<i32>]| {
+                (0..1u32 << n)
+                    .filter(|&bits| satisfies(clauses, bits))
+                    .map(|bits| bits & 0b11)
+                    .collect::<HashSet<_>>()
+            };
+
+            let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.as_slice()));
+            preprocessor.freeze(Var::from_dimacs(1));
+            preprocessor.freeze(Var::from_dimacs(2));
+            preprocessor.probe();
+            preprocessor.substitute_equivalences();
+            let simplified = preprocessor.clauses();
+            assert_eq!(
+                projected(&clauses),
+                projected(&to_ints(&simplified)),
+                "{:?}",
+                clauses
+            );
+
+            let mut cnf = Cnf::from(simplified);
+            match dpll(&mut cnf) {
+                Ok((model, _)) => {
+                    let model = preprocessor.reconstruction().extend(&model);
+                    let bits = (0..n)
+                        .filter(|&i| model.value(Var::from_index(i as usize)) == Some(true))
+                        .fold(0, |bits, i| bits | 1 << i);
+                    assert!(satisfies(&clauses, bits), "{:?}", clauses);
+                }
+                Err(_) => assert!(projected(&clauses).is_empty(), "{:?}", clauses),
+            }
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::BTreeSet;
+
+use num_bigint::{BigUint, RandBigInt};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{compile, Clauses, Ddnnf, DdnnfNode, Lit, PartialSolution, Var};
+
+// the count of each node of the circuit, used to choose between the children of an or node
+// a weighted count comes with the weight of each var when it is free
+#[derive(Debug, Clone)]
+enum Counts {
+    Uniform(Vec<BigUint>),
+    Weighted(Vec<f64>, Vec<f64>),
+}
+
+/// Draws random models of a formula from its decision-DNNF circuit.
+///
+/// Every sample walks down from the root: all the children of an and node are taken, and one
+/// child of an or node is chosen with a probability proportional to the (weighted) count of its
+/// models. The vars left free are set at random. Without weights every model is equally likely.
+///
+/// All the random choices come from a generator seeded by the caller, so the same seed gives the
+/// same samples.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    ddnnf: Ddnnf,
+    vars: Vec<BTreeSet<Var>>,
+    counts: Counts,
+    // the probability of each var of the circuit to be true when it is free
+    positive: Vec<f64>,
+    rng: StdRng,
+}
+
+impl Sampler {
+    /// Samples the models of clauses uniformly.
+    pub fn new(clauses: &Clauses, seed: u64) -> Sampler {
+        Sampler::from_ddnnf(compile(clauses), seed)
+    }
+
+    /// Samples the models of clauses with a probability proportional to the product of the
+    /// weights of their lits.
+    pub fn weighted(clauses: &Clauses, seed: u64, weight: impl Fn(Lit) -> f64) -> Sampler {
+        Sampler::weighted_from_ddnnf(compile(clauses), seed, weight)
+    }
+
+    pub fn from_ddnnf(ddnnf: Ddnnf, seed: u64) -> Sampler {
+        let vars = ddnnf.node_vars();
+        let counts = Counts::Uniform(ddnnf.node_counts(&vars));
+        let positive = vec![0.5; n_var(&ddnnf)];
+        Sampler {
+            ddnnf,
+            vars,
+            counts,
+            positive,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn weighted_from_ddnnf(ddnnf: Ddnnf, seed: u64, weight: impl Fn(Lit) -> f64) -> Sampler {
+        let vars = ddnnf.node_vars();
+        let weights = ddnnf.node_weights(&vars, &weight);
+        let (positive, free) = (0..n_var(&ddnnf))
+            .map(|index| {
+                let var = Var::from_index(index);
+                let free = weight(var.positive()) + weight(var.negative());
+                (weight(var.positive()) / free, free)
+            })
+            .unzip();
+        Sampler {
+            ddnnf,
+            vars,
+            counts: Counts::Weighted(weights, free),
+            positive,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// A random model, all the vars of the circuit are assigned.
+    ///
+    /// Returns None when the formula has no model.
+    pub fn sample(&mut self) -> Option<PartialSolution> {
+        let root = self.ddnnf.root;
+        let empty = match &self.counts {
+            Counts::Uniform(counts) => counts[root].bits() == 0,
+            Counts::Weighted(weights, _) => weights[root] <= 0.0,
+        };
+        if empty {
+            return None;
+        }
+
+        let mut solution = PartialSolution::new(self.positive.len());
+        let mut stack = vec![root];
+        while let Some(index) = stack.pop() {
+            match &self.ddnnf.nodes[index] {
+                DdnnfNode::True | DdnnfNode::False => {}
+                DdnnfNode::Lit(lit) => solution.assign_lit(*lit),
+                DdnnfNode::And(children) => stack.extend(children.iter().cloned()),
+                DdnnfNode::Or(_, children) => {
+                    let children = children.clone();
+                    stack.push(self.choose(index, &children));
+                }
+            }
+        }
+        for &var in self.ddnnf.vars.iter() {
+            if solution.value(var).is_none() {
+                let value = self.rng.gen_bool(self.positive[var.index()]);
+                solution.assign_lit(var.lit(value));
+            }
+        }
+        Some(solution)
+    }
+
+    // choose a child of an or node, proportional to the count of its models
+    fn choose(&mut self, index: usize, children: &[usize]) -> usize {
+        match &self.counts {
+            Counts::Uniform(counts) => {
+                let mut pick = self.rng.gen_biguint_below(&counts[index]);
+                for &child in children {
+                    let gap = self.vars[index].len() - self.vars[child].len();
+                    let count = counts[child].clone() << gap;
+                    if pick < count {
+                        return child;
+                    }
+                    pick -= count;
+                }
+            }
+            Counts::Weighted(weights, free) => {
+                let child_weights = children
+                    .iter()
+                    .map(|&child| {
+                        let gap = self.vars[index].difference(&self.vars[child]);
+                        weights[child] * gap.map(|var| free[var.index()]).product::<f64>()
+                    })
+                    .collect::<Vec<_>>();
+                let mut pick = self.rng.gen::<f64>() * child_weights.iter().sum::<f64>();
+                for (&child, weight) in children.iter().zip(child_weights) {
+                    if pick < weight {
+                        return child;
+                    }
+                    pick -= weight;
+                }
+            }
+        }
+        // rounding, the last child with a count
+        *children.last().unwrap()
+    }
+}
+
+// count of vars up to the largest one of the circuit
+fn n_var(ddnnf: &Ddnnf) -> usize {
+    ddnnf
+        .vars
+        .iter()
+        .next_back()
+        .map_or(0, |var| var.index() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashMap;
+
+    use crate::*;
+
+    #[test]
+    fn sample_uniform() {
+        let clauses = vec![vec![1, 2, 3]];
+        let mut sampler = Sampler::new(&Clauses::from(clauses.as_slice()), 30);
+        let mut seen = HashMap::new();
+        for _ in 0..7000 {
+            let model = sampler.sample().unwrap();
+            assert!(model.is_solved());
+            *seen.entry(model.true_lits()).or_insert(0) += 1;
+        }
+        assert_eq!(seen.len(), 7);
+        assert!(!seen.contains_key(&vec![]));
+        for (_, n) in seen {
+            assert!((800..1200).contains(&n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn sample_weighted() {
+        // with weight 3 for a positive lit and 1 for a negative one:
+        // 1 && 2 weighs 9, each of the other two models 3
+        let clauses = vec![vec![1, 2]];
+        let weight = |lit: Lit| if lit.is_positive() { 3.0 } else { 1.0 };
+        let mut sampler = Sampler::weighted(&Clauses::from(clauses.as_slice()), 30, weight);
+        let both = (0..5000)
+            .filter(|_| sampler.sample().unwrap().true_lits().len() == 2)
+            .count();
+        assert!((2750..3250).contains(&both), "{}", both);
+    }
+
+    #[test]
+    fn sample_seeded() {
+        let clauses = vec![vec![1, -2, 3], vec![-1, 4], vec![2, -4, 5], vec![-3, -5]];
+        let clauses = Clauses::from(clauses.as_slice());
+        let samples = |seed| {
+            let mut sampler = Sampler::new(&clauses, seed);
+            (0..20)
+                .map(|_| sampler.sample().unwrap().true_lits())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(samples(1), samples(1));
+        assert_ne!(samples(1), samples(2));
+
+        // the order of the components does not depend on the run
+        let clauses = (0..8)
+            .map(|i| vec![3 * i + 1, 3 * i + 2, 3 * i + 3])
+            .collect::<Vec<_>>();
+        let clauses = Clauses::from(clauses.as_slice());
+        let mut first = Sampler::new(&clauses, 7);
+        let mut second = Sampler::new(&clauses, 7);
+        for _ in 0..20 {
+            assert_eq!(
+                first.sample().unwrap().true_lits(),
+                second.sample().unwrap().true_lits()
+            );
+        }
+
+        let clauses = vec![vec![1], vec![-1]];
+        let mut sampler = Sampler::new(&Clauses::from(clauses.as_slice()), 1);
+        assert!(sampler.sample().is_none());
+    }
+}
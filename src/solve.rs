@@ -0,0 +1,218 @@
+use std::{collections::BTreeSet, thread};
+
+use crate::{
+    cfcl,
+    count::{components, condition, normalize},
+    horn, horn_renaming, renamed_horn, two_sat, Clause, Clauses, CnfGraph, Lit, PartialSolution,
+    Var,
+};
+
+/// Solves clauses with the engine that fits their shape best.
+///
+/// The units are propagated first, then the formula is split into components that share no vars
+/// and each component is solved on its own: when every clause has at most two lits by
+/// `two_sat`, when it is Horn or becomes Horn when some vars are flipped by `horn`, all of them in
+/// linear time, and otherwise by `cfcl`. The models of the components are combined, the vars that
+/// are left in no clause are not assigned.
+///
+/// Returns None when clauses have no model.
+pub fn solve(clauses: &Clauses) -> Option<PartialSolution> {
+    solve_components(clauses, false)
+}
+
+/// Like `solve`, but the components are solved on all the available cores.
+pub fn solve_parallel(clauses: &Clauses) -> Option<PartialSolution> {
+    solve_components(clauses, true)
+}
+
+fn solve_components(clauses: &Clauses, parallel: bool) -> Option<PartialSolution> {
+    let (mut solution, components) = split(clauses)?;
+    let models = if parallel && components.len() > 1 {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        thread::scope(|scope| {
+            let handles = (0..threads.min(components.len()))
+                .map(|thread| {
+                    let components = &components;
+                    scope.spawn(move || {
+                        components
+                            .iter()
+                            .skip(thread)
+                            .step_by(threads)
+                            .map(|(component, _)| solve_shape(component))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            // thread i solved the components i, i + threads, ..
+            let mut models = vec![None; components.len()];
+            for (thread, handle) in handles.into_iter().enumerate() {
+                let solved = handle.join().unwrap()?;
+                for (index, model) in solved.into_iter().enumerate() {
+                    models[thread + index * threads] = Some(model);
+                }
+            }
+            models.into_iter().collect::<Option<Vec<_>>>()
+        })?
+    } else {
+        components
+            .iter()
+            .map(|(component, _)| solve_shape(component))
+            .collect::<Option<Vec<_>>>()?
+    };
+
+    // back to the vars of clauses
+    for ((_, vars), model) in components.iter().zip(models) {
+        for lit in model.assigned() {
+            solution.assign_lit(vars[lit.index()].lit(lit.is_positive()));
+        }
+    }
+    Some(solution)
+}
+
+// the clauses of a component over its own vars, with the var of the whole formula for each
+type Component = (Clauses, Vec<Var>);
+
+// propagate the units at the top level, then split what is left into clauses that share no vars
+// return None: the units make a clause empty
+pub(crate) fn split(clauses: &Clauses) -> Option<(PartialSolution, Vec<Component>)> {
+    let mut formula = normalize(
+        clauses
+            .0
+            .iter()
+            .map(|clause| clause.inner().to_vec())
+            .collect(),
+    );
+    let mut solution = PartialSolution::new(clauses.2);
+    // units may be all that connects some components
+    while let Some(lit) = formula
+        .iter()
+        .find(|clause| clause.len() <= 1)
+        .map(|clause| clause.first().cloned())
+    {
+        let lit = lit?;
+        solution.assign_lit(lit);
+        formula = condition(&formula, lit)?;
+    }
+
+    let components = components(formula)
+        .into_iter()
+        .map(|component| {
+            let vars = component
+                .iter()
+                .flatten()
+                .map(|lit| lit.var())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>();
+            let index = |var: Var| vars.binary_search(&var).unwrap();
+            let component = component
+                .into_iter()
+                .map(|clause| {
+                    Clause(
+                        clause
+                            .into_iter()
+                            .map(|lit| Lit::from_index(index(lit.var()), lit.is_positive()))
+                            .collect(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            (Clauses::from(component), vars)
+        })
+        .collect();
+    Some((solution, components))
+}
+
+// solve a single component by its shape
+fn solve_shape(clauses: &Clauses) -> Option<PartialSolution> {
+    if clauses.is_2cnf() {
+        return two_sat(clauses).ok();
+    }
+    if clauses.is_horn() {
+        return horn(clauses);
+    }
+    if let Some(flipped) = horn_renaming(clauses) {
+        return renamed_horn(clauses, &flipped);
+    }
+    let mut cnf = CnfGraph::from(clauses.clone());
+    cfcl(&mut cnf).ok().map(|(solution, _)| solution)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{Rng, SeedableRng};
+
+    use super::split;
+    use crate::test_util::satisfied_by;
+    use crate::*;
+
+    #[test]
+    fn solve_split() {
+        // one component, until the unit 5 is propagated
+        let clauses = vec![vec![1, 2, -5], vec![3, 4, -5], vec![5], vec![6, -7]];
+        let formula = Clauses::from(clauses.as_slice());
+        let (units, components) = split(&formula).unwrap();
+        assert_eq!(units.assigned(), vec![Lit::from_dimacs(5)]);
+        // every component is sized by its own vars
+        let mut vars = components
+            .iter()
+            .map(|(component, vars)| {
+                assert_eq!((component.1, component.2), (vars.len(), vars.len()));
+                vars.iter().map(|var| var.to_dimacs()).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        vars.sort();
+        assert_eq!(vars, vec![vec![1, 2], vec![3, 4], vec![6, 7]]);
+        let solution = solve(&formula).unwrap();
+        assert!(satisfied_by(&clauses, &solution));
+
+        let clauses = vec![vec![1, 2], vec![-1], vec![-2]];
+        assert!(split(&Clauses::from(clauses.as_slice())).is_none());
+    }
+
+    #[test]
+    fn solve_random_concatenated() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(41);
+        let n = 12;
+        for _ in 0..30 {
+            // independent random 3-SAT problems over their own vars
+            let mut clauses = vec![];
+            for part in 0..rng.gen_range(1..6) {
+                for _ in 0..rng.gen_range(1..50) {
+                    let clause = (0..3)
+                        .map(|_| {
+                            let var = rng.gen_range(1..=n) + part * n;
+                            var * if rng.gen() { 1 } else { -1 }
+                        })
+                        .collect::<Vec<i32>>();
+                    clauses.push(clause);
+                }
+            }
+            let formula = Clauses::from(clauses.as_slice());
+            let expected = dpll(&mut Cnf::from(formula.clone())).is_ok();
+            for solution in [solve(&formula), solve_parallel(&formula)] {
+                match solution {
+                    Some(solution) => {
+                        assert!(expected, "{:?}", clauses);
+                        assert!(satisfied_by(&clauses, &solution), "{:?}", clauses);
+                    }
+                    None => assert!(!expected, "{:?}", clauses),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solve_large_component() {
+        // a chain where no three vars in a row are equal, one component that is not Horn
+        let n = 1000;
+        let clauses = (1..=n - 2)
+            .flat_map(|i| [vec![i, i + 1, i + 2], vec![-i, -i - 1, -i - 2]])
+            .collect::<Vec<_>>();
+        let formula = Clauses::from(clauses.as_slice());
+        let (_, components) = split(&formula).unwrap();
+        assert_eq!(components.len(), 1);
+        let solution = solve_parallel(&formula).unwrap();
+        assert!(satisfied_by(&clauses, &solution));
+    }
+}
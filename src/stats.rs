@@ -0,0 +1,167 @@
+use std::{fmt, time::Duration};
+
+/// What a search did, the engines keep the statistics of their last search in `stats`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub decisions: usize,
+    // lits assigned by unit propagation
+    pub propagations: usize,
+    pub conflicts: usize,
+    // the times the search started again from the root with the clauses it learned
+    pub restarts: usize,
+    pub learned: usize,
+    // learned clauses that were subsumed or vivified away
+    pub deleted: usize,
+    pub max_level: usize,
+    // the most approximate bytes of the clause database, see `Budget::memory`
+    pub memory: usize,
+    pub time: Times,
+}
+
+/// The time spent in each phase of a search.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Times {
+    pub total: Duration,
+    pub propagation: Duration,
+    // learning a clause from each conflict
+    pub analysis: Duration,
+    // subsumption and vivification of the learned clauses
+    pub reduction: Duration,
+    pub local_search: Duration,
+}
+
+impl Stats {
+    /// The statistics as a JSON object, the times are in seconds.
+    pub fn to_json(&self) -> String {
+        format!(
+            concat!(
+                "{{\"decisions\":{},\"propagations\":{},\"conflicts\":{},\"restarts\":{},",
+                "\"learned\":{},\"deleted\":{},\"max_level\":{},\"memory\":{},",
+                "\"time\":{{\"total\":{},\"propagation\":{},\"analysis\":{},\"reduction\":{},",
+                "\"local_search\":{}}}}}"
+            ),
+            self.decisions,
+            self.propagations,
+            self.conflicts,
+            self.restarts,
+            self.learned,
+            self.deleted,
+            self.max_level,
+            self.memory,
+            self.time.total.as_secs_f64(),
+            self.time.propagation.as_secs_f64(),
+            self.time.analysis.as_secs_f64(),
+            self.time.reduction.as_secs_f64(),
+            self.time.local_search.as_secs_f64(),
+        )
+    }
+}
+
+// one line for each statistic
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "decisions: {}", self.decisions)?;
+        writeln!(f, "propagations: {}", self.propagations)?;
+        writeln!(f, "conflicts: {}", self.conflicts)?;
+        writeln!(f, "restarts: {}", self.restarts)?;
+        writeln!(f, "learned: {}", self.learned)?;
+        writeln!(f, "deleted: {}", self.deleted)?;
+        writeln!(f, "max level: {}", self.max_level)?;
+        writeln!(f, "memory: {} bytes", self.memory)?;
+        writeln!(f, "time: {:.3} s", self.time.total.as_secs_f64())?;
+        writeln!(
+            f,
+            "  propagation: {:.3} s",
+            self.time.propagation.as_secs_f64()
+        )?;
+        writeln!(f, "  analysis: {:.3} s", self.time.analysis.as_secs_f64())?;
+        writeln!(f, "  reduction: {:.3} s", self.time.reduction.as_secs_f64())?;
+        write!(
+            f,
+            "  local search: {:.3} s",
+            self.time.local_search.as_secs_f64()
+        )
+    }
+}
+
+// the head of the progress table, as in MiniSat
+pub(crate) const PROGRESS_HEADER: [&str; 3] = [
+    "============================[ Search Statistics ]=============================",
+    "| Conflicts | Decisions | Propagations | Learned | Deleted | Level |     Time |",
+    "==============================================================================",
+];
+
+impl Stats {
+    // a row of the progress table
+    pub(crate) fn progress(&self, elapsed: Duration) -> String {
+        format!(
+            "| {:>9} | {:>9} | {:>12} | {:>7} | {:>7} | {:>5} | {:>7.2}s |",
+            self.conflicts,
+            self.decisions,
+            self.propagations,
+            self.learned,
+            self.deleted,
+            self.max_level,
+            elapsed.as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use crate::*;
+
+    #[test]
+    fn stats_json() {
+        let stats = Stats {
+            decisions: 3,
+            conflicts: 1,
+            time: Times {
+                total: Duration::from_millis(1500),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.to_json(),
+            concat!(
+                "{\"decisions\":3,\"propagations\":0,\"conflicts\":1,\"restarts\":0,",
+                "\"learned\":0,\"deleted\":0,\"max_level\":0,\"memory\":0,",
+                "\"time\":{\"total\":1.5,\"propagation\":0,\"analysis\":0,\"reduction\":0,",
+                "\"local_search\":0}}"
+            )
+        );
+    }
+
+    #[test]
+    fn stats_engines() {
+        let clauses = vec![
+            vec![-2, -3, -4, 5],
+            vec![-1, -5, 6],
+            vec![-5, 7],
+            vec![-1, -6, -7],
+            vec![-1, -2, 5],
+            vec![-1, -3, 5],
+            vec![-1, -4, 5],
+            vec![1, 4],
+            vec![-1, 2, 3, 4, 5, -6],
+        ];
+        let mut cnf = CnfGraph::from(Clauses::from(clauses.as_slice()));
+        cnf.progress = Some(1);
+        assert!(cfcl_limited(&mut cnf, &Budget::default()).is_sat());
+        let stats = &cnf.stats;
+        assert_eq!(stats.decisions, cnf.decisions.len());
+        assert_eq!(stats.learned, stats.conflicts);
+        assert!(stats.max_level >= 1 && stats.max_level <= stats.decisions);
+        assert!(stats.memory > 0);
+        assert!(stats.time.total >= stats.time.propagation + stats.time.analysis);
+
+        let mut cnf = Cnf::from(Clauses::from(clauses.as_slice()));
+        assert!(dpll(&mut cnf).is_ok());
+        assert_eq!(cnf.stats.decisions, cnf.decisions.len());
+        assert_eq!(cnf.stats.learned, 0);
+    }
+}
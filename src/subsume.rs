@@ -0,0 +1,115 @@
+use crate::{Clause, Clauses, Lit, Preprocessor};
+
+/// What a subsumption pass did to the clauses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Subsumption {
+    // subsumed clauses, duplicates included
+    pub removed: usize,
+    // lits removed by self-subsuming resolution
+    pub strengthened: usize,
+}
+
+impl Preprocessor {
+    /// Removes the clauses that are subsumed by another clause, and strengthens a clause by
+    /// self-subsuming resolution when another clause subsumes it with one lit flipped.
+    ///
+    /// The clauses that were added or shortened since the last call are used to subsume the
+    /// others, the clauses that it shortens are used in turn. The candidates come from the
+    /// shortest occurrence list of the clause, and most of them are ruled out by comparing the
+    /// signatures of the clauses before their lits are.
+    pub fn subsume(&mut self) -> Subsumption {
+        let mut res = Subsumption::default();
+        while let Some(id) = self.queue.pop() {
+            if self.unsat {
+                break;
+            }
+            self.subsume_with(id, &mut res);
+        }
+        res
+    }
+
+    fn subsume_with(&mut self, id: usize, res: &mut Subsumption) {
+        let clause = match self.clauses[id].clone() {
+            Some(clause) => clause,
+            None => return,
+        };
+        // a clause that is subsumed contains every lit of clause, so only the shortest
+        // occurrence list of its lits has to be checked
+        let lit = match clause.iter().min_by_key(|lit| self.occurs(**lit).len()) {
+            Some(&lit) => lit,
+            None => return,
+        };
+        for other in self.occurs(lit) {
+            if other != id && self.is_subset(id, &clause, other, None) {
+                self.remove_clause(other);
+                res.removed += 1;
+            }
+        }
+
+        // self-subsuming resolution: clause with lit flipped subsumes other, the resolvent on
+        // lit is other without !lit
+        for &lit in clause.iter() {
+            for other in self.occurs(!lit) {
+                if other != id && self.is_subset(id, &clause, other, Some(lit)) {
+                    self.strengthen(other, !lit);
+                    res.strengthened += 1;
+                }
+            }
+        }
+    }
+
+    // every lit of clause id is in the clause other, flipped is looked up negated
+    // the signatures only depend on the vars, so they also hold with a flipped lit
+    fn is_subset(&self, id: usize, clause: &[Lit], other: usize, flipped: Option<Lit>) -> bool {
+        if self.signatures[id] & !self.signatures[other] != 0 {
+            return false;
+        }
+        match &self.clauses[other] {
+            Some(other) => {
+                other.len() >= clause.len()
+                    && clause.iter().all(|&lit| {
+                        let lit = if Some(lit) == flipped { !lit } else { lit };
+                        other.binary_search(&lit).is_ok()
+                    })
+            }
+            None => false,
+        }
+    }
+}
+
+// remove the subsumed clauses from a list of clauses and strengthen the others between themselves
+pub(crate) fn subsume_clauses(clauses: &[Clause]) -> (Vec<Clause>, Subsumption) {
+    let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.to_vec()));
+    let res = preprocessor.subsume();
+    (preprocessor.clauses().0, res)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::*;
+
+    #[test]
+    fn subsume_counts() {
+        let clauses = vec![
+            vec![1, 2],
+            vec![1, 2, 3],
+            vec![2, 1],
+            vec![-1, 2, 4],
+            vec![3, 4, 5],
+        ];
+        let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.as_slice()));
+        let res = preprocessor.subsume();
+        // 1 || 2 strengthens -1 || 2 || 4 to 2 || 4, the superset and the duplicate are removed
+        assert_eq!(res.removed, 2);
+        assert_eq!(res.strengthened, 1);
+        let mut left = preprocessor
+            .clauses()
+            .0
+            .iter()
+            .map(|clause| clause.inner().iter().map(|lit| lit.to_dimacs()).collect())
+            .collect::<Vec<Vec<isize>>>();
+        left.sort();
+        assert_eq!(left, vec![vec![1, 2], vec![2, 4], vec![3, 4, 5]]);
+    }
+}
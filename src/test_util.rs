@@ -0,0 +1,52 @@
+// formulas and checks shared by the tests, the clauses are in dimacs lits
+
+use rand::Rng;
+
+use crate::{Lit, PartialSolution};
+
+// bit i of bits is the value of var i + 1
+pub(crate) fn satisfies(clauses: &[Vec<i32>], bits: u32) -> bool {
+    clauses.iter().all(|clause| {
+        clause
+            .iter()
+            .any(|&lit| (bits >> (lit.abs() - 1) & 1 == 1) == (lit > 0))
+    })
+}
+
+// the vars that are not assigned are false
+pub(crate) fn satisfied_by(clauses: &[Vec<i32>], solution: &PartialSolution) -> bool {
+    clauses.iter().all(|clause| {
+        clause.iter().any(|&lit| {
+            let lit = Lit::from_dimacs(lit as isize);
+            solution.value(lit.var()).unwrap_or(false) == lit.is_positive()
+        })
+    })
+}
+
+// a lit of one of the vars 1 to n
+pub(crate) fn random_lit(rng: &mut impl Rng, n: i32) -> i32 {
+    rng.gen_range(1..=n) * if rng.gen() { 1 } else { -1 }
+}
+
+// m clauses of 3 random lits over n vars
+pub(crate) fn random_3sat(rng: &mut impl Rng, n: i32, m: usize) -> Vec<Vec<i32>> {
+    (0..m)
+        .map(|_| (0..3).map(|_| random_lit(rng, n)).collect())
+        .collect()
+}
+
+// random 3-SAT with a hidden model, the clauses that the model falsifies are drawn again
+pub(crate) fn planted(rng: &mut impl Rng, n: i32, m: usize) -> Vec<Vec<i32>> {
+    let model = (0..n).map(|_| rng.gen::<bool>()).collect::<Vec<_>>();
+    let mut clauses = vec![];
+    while clauses.len() < m {
+        let clause = (0..3).map(|_| random_lit(rng, n)).collect::<Vec<i32>>();
+        if clause
+            .iter()
+            .any(|&lit| model[lit.unsigned_abs() as usize - 1] == (lit > 0))
+        {
+            clauses.push(clause);
+        }
+    }
+    clauses
+}
@@ -0,0 +1,529 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    cfcl_observed, dpll_observed, Budget, Clause, Cnf, CnfGraph, Dimacs, Lit, PartialSolution,
+    SearchObserver, SolveResult, Strategy, Terminate,
+};
+
+// the first bytes of a trace, with the version of its format
+const MAGIC: &[u8; 4] = b"DRST";
+const VERSION: u8 = 1;
+
+/// The engine of a traced search, with what its search depends on besides the seed.
+#[derive(Debug, Clone, Copy)]
+pub enum Engine {
+    Dpll,
+    Cfcl(Strategy),
+}
+
+/// An event of a search, as `SearchObserver` reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    // a guess and the level it opens
+    Decision(Lit, usize),
+    // an implied lit and the id of its reason clause
+    Propagation(Lit, Option<usize>),
+    // the id of the clause in conflict
+    Conflict(usize),
+    Learned(Vec<Lit>),
+    // the level the search goes back to
+    Backtrack(usize),
+    Restart,
+    Solution,
+    // the search is done, the trace of a search that was stopped has no end
+    End,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Decision(lit, level) => write!(f, "decision {} at level {}", lit, level),
+            Event::Propagation(lit, Some(reason)) => {
+                write!(f, "propagation {} by c{}", lit, reason)
+            }
+            Event::Propagation(lit, None) => write!(f, "propagation {}", lit),
+            Event::Conflict(clause_id) => write!(f, "conflict of c{}", clause_id),
+            Event::Learned(lits) => {
+                write!(f, "learned")?;
+                for lit in lits {
+                    write!(f, " {}", lit)?;
+                }
+                Ok(())
+            }
+            Event::Backtrack(level) => write!(f, "backtrack to level {}", level),
+            Event::Restart => write!(f, "restart"),
+            Event::Solution => write!(f, "solution"),
+            Event::End => write!(f, "end of the search"),
+        }
+    }
+}
+
+// a byte for the kind of event, then its numbers as LEB128
+fn encode(buffer: &mut Vec<u8>, event: &Event) {
+    fn number(buffer: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buffer.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        buffer.push(value as u8);
+    }
+
+    match event {
+        Event::Decision(lit, level) => {
+            buffer.push(0);
+            number(buffer, lit.code() as u64);
+            number(buffer, *level as u64);
+        }
+        Event::Propagation(lit, reason) => {
+            buffer.push(1);
+            number(buffer, lit.code() as u64);
+            // 0 for no reason, the reason ids are never usize::MAX
+            number(buffer, reason.map_or(0, |reason| reason as u64 + 1));
+        }
+        Event::Conflict(clause_id) => {
+            buffer.push(2);
+            number(buffer, *clause_id as u64);
+        }
+        Event::Learned(lits) => {
+            buffer.push(3);
+            number(buffer, lits.len() as u64);
+            for lit in lits {
+                number(buffer, lit.code() as u64);
+            }
+        }
+        Event::Backtrack(level) => {
+            buffer.push(4);
+            number(buffer, *level as u64);
+        }
+        Event::Restart => buffer.push(5),
+        Event::Solution => buffer.push(6),
+        Event::End => buffer.push(7),
+    }
+}
+
+// the bytes of a trace that are not decoded yet
+struct Decoder<'a>(&'a [u8]);
+
+impl Decoder<'_> {
+    fn byte(&mut self) -> io::Result<u8> {
+        let (&byte, rest) = self
+            .0
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated trace"))?;
+        self.0 = rest;
+        Ok(byte)
+    }
+
+    fn number(&mut self) -> io::Result<usize> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(value).map_err(|_| invalid("number out of range"));
+            }
+        }
+        Err(invalid("number out of range"))
+    }
+
+    fn lit(&mut self) -> io::Result<Lit> {
+        self.number().map(Lit::from_code)
+    }
+
+    fn event(&mut self) -> io::Result<Event> {
+        Ok(match self.byte()? {
+            0 => Event::Decision(self.lit()?, self.number()?),
+            1 => {
+                let lit = self.lit()?;
+                Event::Propagation(lit, self.number()?.checked_sub(1))
+            }
+            2 => Event::Conflict(self.number()?),
+            3 => {
+                let len = self.number()?;
+                Event::Learned((0..len).map(|_| self.lit()).collect::<io::Result<_>>()?)
+            }
+            4 => Event::Backtrack(self.number()?),
+            5 => Event::Restart,
+            6 => Event::Solution,
+            7 => Event::End,
+            kind => return Err(invalid(&format!("unknown event {}", kind))),
+        })
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A search recorded by `TraceWriter`.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub engine: Engine,
+    pub seed: u64,
+    pub events: Vec<Event>,
+}
+
+impl Trace {
+    pub fn read(mut reader: impl Read) -> io::Result<Trace> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        let mut decoder = Decoder(&bytes);
+        let mut magic = [0; 4];
+        for byte in magic.iter_mut() {
+            *byte = decoder.byte()?;
+        }
+        if &magic != MAGIC || decoder.byte()? != VERSION {
+            return Err(invalid("not a trace of this version"));
+        }
+        let engine = match decoder.byte()? {
+            0 => Engine::Dpll,
+            1 => Engine::Cfcl(Strategy::Direct),
+            2 => Engine::Cfcl(Strategy::Random),
+            engine => return Err(invalid(&format!("unknown engine {}", engine))),
+        };
+        let mut seed = [0; 8];
+        for byte in seed.iter_mut() {
+            *byte = decoder.byte()?;
+        }
+        let mut events = vec![];
+        while !decoder.0.is_empty() {
+            events.push(decoder.event()?);
+        }
+        Ok(Trace {
+            engine,
+            seed: u64::from_le_bytes(seed),
+            events,
+        })
+    }
+}
+
+/// Writes the events of a search as they happen, in a compact binary format that `Trace::read`
+/// reads back.
+///
+/// The first error of the writer stops the trace, `finish` returns it.
+pub struct TraceWriter<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> TraceWriter<W> {
+    /// The trace of a search of engine whose cnf is seeded with seed.
+    pub fn new(mut writer: W, engine: Engine, seed: u64) -> io::Result<TraceWriter<W>> {
+        let engine = match engine {
+            Engine::Dpll => 0,
+            Engine::Cfcl(Strategy::Direct) => 1,
+            Engine::Cfcl(Strategy::Random) => 2,
+        };
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, engine])?;
+        writer.write_all(&seed.to_le_bytes())?;
+        Ok(TraceWriter {
+            writer,
+            buffer: vec![],
+            error: None,
+        })
+    }
+
+    fn record(&mut self, event: Event) {
+        if self.error.is_none() {
+            self.buffer.clear();
+            encode(&mut self.buffer, &event);
+            self.error = self.writer.write_all(&self.buffer).err();
+        }
+    }
+
+    /// End the trace of a search with its result, a search that was stopped gets no end event.
+    pub fn finish(mut self, result: &SolveResult) -> io::Result<W> {
+        if !result.is_unknown() {
+            self.record(Event::End);
+        }
+        match self.error {
+            Some(error) => Err(error),
+            None => self.writer.flush().map(|_| self.writer),
+        }
+    }
+}
+
+impl<W: Write> SearchObserver for TraceWriter<W> {
+    fn on_decision(&mut self, lit: Lit, level: usize) {
+        self.record(Event::Decision(lit, level));
+    }
+
+    fn on_propagation(&mut self, lit: Lit, reason: Option<usize>) {
+        self.record(Event::Propagation(lit, reason));
+    }
+
+    fn on_conflict(&mut self, clause_id: usize, _clause: &dyn Fn() -> Clause) {
+        self.record(Event::Conflict(clause_id));
+    }
+
+    fn on_learned(&mut self, clause: &Clause) {
+        self.record(Event::Learned(clause.inner().to_vec()));
+    }
+
+    fn on_backtrack(&mut self, level: usize) {
+        self.record(Event::Backtrack(level));
+    }
+
+    fn on_restart(&mut self) {
+        self.record(Event::Restart);
+    }
+
+    fn on_solution(&mut self, _solution: &PartialSolution) {
+        self.record(Event::Solution);
+    }
+}
+
+/// The first event where a replayed search leaves its trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    // the index of the event in the trace
+    pub index: usize,
+    pub expected: Event,
+    pub found: Event,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "event {}: expected {}, found {}",
+            self.index, self.expected, self.found
+        )
+    }
+}
+
+// compare the events of a search with a trace, and stop the search at the first divergence, or
+// at the end of a trace that has no end event
+struct Replay<'a> {
+    events: &'a [Event],
+    index: usize,
+    divergence: Option<Divergence>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Replay<'_> {
+    fn check(&mut self, found: Event) {
+        if self.stop.load(Ordering::Relaxed) {
+            return;
+        }
+        match self.events.get(self.index) {
+            Some(expected) if *expected == found => self.index += 1,
+            Some(expected) => {
+                self.divergence = Some(Divergence {
+                    index: self.index,
+                    expected: expected.clone(),
+                    found,
+                });
+            }
+            None => {}
+        }
+        if self.divergence.is_some() || self.index == self.events.len() {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl SearchObserver for Replay<'_> {
+    fn on_decision(&mut self, lit: Lit, level: usize) {
+        self.check(Event::Decision(lit, level));
+    }
+
+    fn on_propagation(&mut self, lit: Lit, reason: Option<usize>) {
+        self.check(Event::Propagation(lit, reason));
+    }
+
+    fn on_conflict(&mut self, clause_id: usize, _clause: &dyn Fn() -> Clause) {
+        self.check(Event::Conflict(clause_id));
+    }
+
+    fn on_learned(&mut self, clause: &Clause) {
+        self.check(Event::Learned(clause.inner().to_vec()));
+    }
+
+    fn on_backtrack(&mut self, level: usize) {
+        self.check(Event::Backtrack(level));
+    }
+
+    fn on_restart(&mut self) {
+        self.check(Event::Restart);
+    }
+
+    fn on_solution(&mut self, _solution: &PartialSolution) {
+        self.check(Event::Solution);
+    }
+}
+
+/// Search the formula again as the trace was recorded and compare the events with it.
+///
+/// Return the count of events that were replayed: all the events of the trace, or return the
+/// first divergence.
+pub fn replay(dimacs: &Dimacs, trace: &Trace) -> Result<usize, Divergence> {
+    let stop = Arc::new(AtomicBool::new(trace.events.is_empty()));
+    let budget = Budget {
+        terminate: Some(Terminate::Flag(stop.clone())),
+        ..Default::default()
+    };
+    let mut replay = Replay {
+        events: &trace.events,
+        index: 0,
+        divergence: None,
+        stop,
+    };
+    match trace.engine {
+        Engine::Dpll => {
+            let mut cnf = Cnf::from(dimacs.clone());
+            cnf.set_seed(trace.seed);
+            dpll_observed(&mut cnf, &budget, &mut replay);
+        }
+        Engine::Cfcl(strategy) => {
+            let mut cnf = CnfGraph::from(dimacs.clone());
+            cnf.set_seed(trace.seed);
+            cnf.strategy = strategy;
+            cfcl_observed(&mut cnf, &budget, &mut replay);
+        }
+    }
+    replay.check(Event::End);
+    match replay.divergence {
+        Some(divergence) => Err(divergence),
+        None => Ok(replay.index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util;
+    use crate::*;
+
+    fn random_3sat(rng: &mut impl Rng, n: i32, m: usize) -> Dimacs {
+        Dimacs {
+            n_var: n as usize,
+            clauses: Clauses::from(test_util::random_3sat(rng, n, m).as_slice()),
+            xors: vec![],
+        }
+    }
+
+    // the bytes of the trace of a search
+    fn record(dimacs: &Dimacs, engine: Engine, seed: u64, budget: &Budget) -> Vec<u8> {
+        let mut writer = TraceWriter::new(vec![], engine, seed).unwrap();
+        let res = match engine {
+            Engine::Dpll => {
+                let mut cnf = Cnf::from(dimacs.clone());
+                cnf.set_seed(seed);
+                dpll_observed(&mut cnf, budget, &mut writer)
+            }
+            Engine::Cfcl(strategy) => {
+                let mut cnf = CnfGraph::from(dimacs.clone());
+                cnf.set_seed(seed);
+                cnf.strategy = strategy;
+                cfcl_observed(&mut cnf, budget, &mut writer)
+            }
+        };
+        writer.finish(&res).unwrap()
+    }
+
+    #[test]
+    fn trace_replay() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(50);
+        let engines = [
+            Engine::Dpll,
+            Engine::Cfcl(Strategy::Direct),
+            Engine::Cfcl(Strategy::Random),
+        ];
+        for _ in 0..5 {
+            let dimacs = random_3sat(&mut rng, 30, 128);
+            for engine in engines {
+                let bytes = record(&dimacs, engine, 7, &Budget::default());
+                let trace = Trace::read(bytes.as_slice()).unwrap();
+                assert_eq!(trace.seed, 7);
+                assert_eq!(trace.events.last(), Some(&Event::End));
+                assert_eq!(replay(&dimacs, &trace), Ok(trace.events.len()));
+
+                // a trace cut short is replayed as far as it goes
+                let mut prefix = trace.clone();
+                prefix.events.truncate(trace.events.len() / 2);
+                assert_eq!(replay(&dimacs, &prefix), Ok(prefix.events.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn trace_divergence() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(51);
+        let dimacs = random_3sat(&mut rng, 30, 128);
+        let bytes = record(
+            &dimacs,
+            Engine::Cfcl(Strategy::Direct),
+            0,
+            &Budget::default(),
+        );
+        let trace = Trace::read(bytes.as_slice()).unwrap();
+        let index = trace
+            .events
+            .iter()
+            .position(|event| matches!(event, Event::Decision(..)))
+            .unwrap();
+
+        // the search takes the other value of a guess
+        let mut changed = trace.clone();
+        let Event::Decision(lit, level) = trace.events[index] else {
+            unreachable!()
+        };
+        changed.events[index] = Event::Decision(!lit, level);
+        assert_eq!(
+            replay(&dimacs, &changed),
+            Err(Divergence {
+                index,
+                expected: Event::Decision(!lit, level),
+                found: Event::Decision(lit, level),
+            })
+        );
+
+        // the formula has one more clause, that is not in the trace
+        let mut other = dimacs.clone();
+        other.clauses.0.push(Clause::from(vec![1, 2]));
+        assert!(replay(&other, &trace).is_err());
+
+        // a search that ends before its trace
+        let mut longer = trace.clone();
+        longer
+            .events
+            .insert(longer.events.len() - 1, Event::Restart);
+        let divergence = replay(&dimacs, &longer).unwrap_err();
+        assert_eq!(divergence.expected, Event::Restart);
+        assert_eq!(divergence.found, Event::End);
+    }
+
+    #[test]
+    fn trace_stopped() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(52);
+        let dimacs = random_3sat(&mut rng, 50, 213);
+        let budget = Budget {
+            conflicts: Some(10),
+            ..Default::default()
+        };
+        let bytes = record(&dimacs, Engine::Cfcl(Strategy::Direct), 0, &budget);
+        let trace = Trace::read(bytes.as_slice()).unwrap();
+        assert!(!trace.events.contains(&Event::End));
+        assert_eq!(replay(&dimacs, &trace), Ok(trace.events.len()));
+
+        // the header and the kind of the first event, without its numbers
+        assert!(matches!(
+            trace.events[0],
+            Event::Decision(..) | Event::Propagation(..)
+        ));
+        assert!(Trace::read(&bytes[..15]).is_err());
+        assert!(Trace::read(&b"DRST\x02"[..]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{HashMap, VecDeque};
+
+use petgraph::{algo::kosaraju_scc, graph::DiGraph, prelude::NodeIndex};
+
+use crate::{Clauses, Lit, PartialSolution};
+
+impl Clauses {
+    // every clause has at most two lits
+    pub fn is_2cnf(&self) -> bool {
+        self.0.iter().all(|clause| clause.inner().len() <= 2)
+    }
+}
+
+/// Solves a formula where every clause has at most two lits, in linear time.
+///
+/// A clause `a || b` is the two implications `!a -> b` and `!b -> a`, a unit clause `a` is
+/// `!a -> a`. The formula is unsatisfiable exactly when a var and its negation are in the same
+/// strongly connected component of this implication graph, and then the cycle `x -> .. -> !x ->
+/// .. -> x` through them is returned, the first and the last lit of the cycle are the same.
+/// Otherwise a lit is made true when its component comes after the one of its negation in the
+/// topological order. An empty clause gives an empty cycle.
+///
+/// Every var up to the largest one is assigned in the model.
+pub fn two_sat(clauses: &Clauses) -> Result<PartialSolution, Vec<Lit>> {
+    assert!(
+        clauses.is_2cnf(),
+        "two_sat needs clauses of at most two lits"
+    );
+    let n_var = clauses.2;
+    // the node of a lit is its code
+    let mut graph = DiGraph::<Lit, ()>::with_capacity(2 * n_var, 2 * clauses.0.len());
+    for code in 0..2 * n_var {
+        graph.add_node(Lit::from_code(code));
+    }
+    let node = |lit: Lit| NodeIndex::new(lit.code());
+    for clause in clauses.0.iter() {
+        match *clause.inner() {
+            [] => return Err(vec![]),
+            [a] => {
+                graph.add_edge(node(!a), node(a), ());
+            }
+            [a, b] => {
+                graph.add_edge(node(!a), node(b), ());
+                graph.add_edge(node(!b), node(a), ());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // the components come out in reverse topological order
+    let mut component = vec![0; 2 * n_var];
+    for (index, nodes) in kosaraju_scc(&graph).into_iter().enumerate() {
+        for node in nodes {
+            component[node.index()] = index;
+        }
+    }
+    let mut solution = PartialSolution::new(n_var);
+    for index in 0..n_var {
+        let lit = Lit::from_index(index, true);
+        let (pos, neg) = (component[lit.code()], component[(!lit).code()]);
+        if pos == neg {
+            let mut cycle = path(&graph, lit, !lit);
+            cycle.extend(path(&graph, !lit, lit).into_iter().skip(1));
+            return Err(cycle);
+        }
+        solution.assign_lit(if pos < neg { lit } else { !lit });
+    }
+    Ok(solution)
+}
+
+// the shortest path of implications from one lit to another one that it implies
+fn path(graph: &DiGraph<Lit, ()>, from: Lit, to: Lit) -> Vec<Lit> {
+    let mut parent = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(lit) = queue.pop_front() {
+        if lit == to {
+            break;
+        }
+        for next in graph.neighbors(NodeIndex::new(lit.code())) {
+            let next = graph[next];
+            if next != from && !parent.contains_key(&next) {
+                parent.insert(next, lit);
+                queue.push_back(next);
+            }
+        }
+    }
+    let mut res = vec![to];
+    let mut lit = to;
+    while lit != from {
+        lit = parent[&lit];
+        res.push(lit);
+    }
+    res.reverse();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{Rng, SeedableRng};
+
+    use crate::test_util::{random_lit, satisfies};
+    use crate::*;
+
+    #[test]
+    fn two_sat_cycle() {
+        // 1 -> !2 -> !1 -> !3 -> 1, the other way round works too
+        let clauses = vec![vec![-1, 2], vec![-2, -1], vec![1, 3], vec![-3, 1]];
+        let cycle = two_sat(&Clauses::from(clauses.as_slice())).unwrap_err();
+        let cycle = cycle.iter().map(|lit| lit.to_dimacs()).collect::<Vec<_>>();
+        assert_eq!(cycle, vec![1, -2, -1, -3, 1]);
+
+        let clauses = vec![vec![1, 2, 3]];
+        assert!(!Clauses::from(clauses.as_slice()).is_2cnf());
+    }
+
+    #[test]
+    fn two_sat_random() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(39);
+        let n = 8;
+        for _ in 0..500 {
+            let clauses = (0..rng.gen_range(1..20))
+                .map(|_| {
+                    (0..rng.gen_range(1..=2))
+                        .map(|_| random_lit(&mut rng, n))
+                        .collect()
+                })
+                .collect::<Vec<Vec<i32>>>();
+            let expected = (0..1u32 << n).any(|bits| satisfies(&clauses, bits));
+            let formula = Clauses::from(clauses.as_slice());
+            match two_sat(&formula) {
+                Ok(solution) => {
+                    assert!(expected, "{:?}", clauses);
+                    let bits = solution
+                        .true_lits()
+                        .iter()
+                        .fold(0, |bits, index| bits | 1 << index);
+                    assert!(satisfies(&clauses, bits), "{:?}", clauses);
+                }
+                Err(cycle) => {
+                    assert!(!expected, "{:?}", clauses);
+                    assert_eq!(cycle.first(), cycle.last());
+                    assert!(cycle.contains(&!cycle[0]));
+                    // every step is the implication of a clause
+                    for pair in cycle.windows(2) {
+                        let (a, b) = (pair[0].to_dimacs() as i32, pair[1].to_dimacs() as i32);
+                        assert!(
+                            clauses.iter().any(|clause| {
+                                let mut clause = clause.clone();
+                                clause.sort();
+                                clause.dedup();
+                                let mut edge = vec![-a, b];
+                                edge.sort();
+                                edge.dedup();
+                                clause == edge
+                            }),
+                            "{:?} {:?}",
+                            clauses,
+                            cycle
+                        );
+                    }
+                }
+            }
+            assert_eq!(solve(&formula).is_some(), expected, "{:?}", clauses);
+        }
+    }
+}
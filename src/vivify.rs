@@ -0,0 +1,146 @@
+use crate::{preprocess::signature, Clause, Clauses, Lit, Preprocessor};
+
+/// What a vivification pass did to the clauses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Vivification {
+    pub shortened: usize,
+    // clauses that the others imply
+    pub removed: usize,
+    // lits assigned by unit propagation, counted against the budget
+    pub propagations: usize,
+}
+
+impl Preprocessor {
+    /// Vivifies every clause, until `budget` propagations are spent.
+    ///
+    /// The negations of the lits of a clause are assumed one at a time, with unit propagation on
+    /// the other clauses after each one. A lit that is already false is dropped from the clause,
+    /// and the clause ends at a lit that is already true or at a conflict. A clause that keeps all
+    /// its lits this way is implied by the others and is removed.
+    pub fn vivify(&mut self, budget: usize) -> Vivification {
+        let ids = (0..self.clauses.len()).collect::<Vec<_>>();
+        self.vivify_clauses(&ids, budget)
+    }
+
+    pub(crate) fn vivify_clauses(&mut self, ids: &[usize], budget: usize) -> Vivification {
+        let mut res = Vivification::default();
+        for &id in ids {
+            if res.propagations >= budget || self.unsat {
+                break;
+            }
+            // the clause itself does not take part in the propagation
+            let clause = match self.remove_clause(id) {
+                Some(clause) => clause,
+                None => continue,
+            };
+            let (kept, implied) = self.vivify_clause(&clause, &mut res.propagations);
+            if implied && kept.len() == clause.len() {
+                res.removed += 1;
+            } else {
+                if kept.len() < clause.len() {
+                    res.shortened += 1;
+                }
+                self.restore_clause(id, kept);
+            }
+        }
+        self.subsume();
+        res
+    }
+
+    // the lits of clause that are needed, the lits that the others make false when the ones
+    // before are false are left out
+    // return (_, true): the kept lits are implied by the other clauses
+    fn vivify_clause(&self, clause: &[Lit], propagations: &mut usize) -> (Vec<Lit>, bool) {
+        let mut kept = vec![];
+        for &lit in clause {
+            let assumed = kept.iter().map(|&lit: &Lit| !lit).collect::<Vec<_>>();
+            let implied = match self.implied(&assumed) {
+                Some(implied) => implied,
+                None => return (kept, true),
+            };
+            *propagations += implied.len();
+            if implied.contains(&lit) {
+                kept.push(lit);
+                return (kept, true);
+            }
+            if !implied.contains(&!lit) {
+                kept.push(lit);
+            }
+        }
+        let assumed = kept.iter().map(|&lit| !lit).collect::<Vec<_>>();
+        match self.implied(&assumed) {
+            Some(implied) => {
+                *propagations += implied.len();
+                (kept, false)
+            }
+            None => (kept, true),
+        }
+    }
+
+    // put a clause back at its id
+    fn restore_clause(&mut self, id: usize, mut clause: Vec<Lit>) {
+        clause.sort();
+        if clause.is_empty() {
+            self.unsat = true;
+        }
+        for &lit in clause.iter() {
+            self.occurrences.entry(lit).or_default().insert(id);
+        }
+        self.signatures[id] = signature(&clause);
+        self.clauses[id] = Some(clause);
+        self.queue.push(id);
+    }
+}
+
+// vivify the learned clauses with the help of the original clauses
+// return a single empty clause when the formula turns out to be unsatisfiable
+pub(crate) fn vivify_learned(
+    original: &[Clause],
+    learned: &[Clause],
+    budget: usize,
+) -> (Vec<Clause>, Vivification) {
+    let mut preprocessor = Preprocessor::new(&Clauses::from(original.to_vec()));
+    let ids = learned
+        .iter()
+        .filter_map(|clause| preprocessor.add_clause(clause.inner().to_vec()))
+        .collect::<Vec<_>>();
+    let res = preprocessor.vivify_clauses(&ids, budget);
+    if preprocessor.unsat {
+        return (vec![Clause(vec![])], res);
+    }
+    let learned = ids
+        .into_iter()
+        .filter_map(|id| preprocessor.clauses[id].clone().map(Clause))
+        .collect();
+    (learned, res)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::*;
+
+    #[test]
+    fn vivify_small() {
+        // !1 implies 2 through the first two clauses, so 1 || 2 is implied,
+        // and !1 makes !6 false, so 1 || !6 || 7 becomes 1 || 7
+        let clauses = vec![vec![1, 6], vec![-6, 2], vec![1, 2], vec![1, -6, 7]];
+        let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.as_slice()));
+        let res = preprocessor.vivify(1000);
+        assert_eq!(res.removed, 1);
+        assert_eq!(res.shortened, 1);
+        assert!(res.propagations > 0);
+        let mut left = preprocessor
+            .clauses()
+            .0
+            .iter()
+            .map(|clause| clause.inner().iter().map(|lit| lit.to_dimacs()).collect())
+            .collect::<Vec<Vec<isize>>>();
+        left.sort();
+        assert_eq!(left, vec![vec![1, 6], vec![1, 7], vec![2, -6]]);
+
+        // no budget, nothing changes
+        let mut preprocessor = Preprocessor::new(&Clauses::from(clauses.as_slice()));
+        assert_eq!(preprocessor.vivify(0), Vivification::default());
+    }
+}
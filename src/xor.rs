@@ -0,0 +1,252 @@
+use std::collections::BTreeSet;
+
+use crate::{Clause, Lit, Var};
+
+// one row of the matrix: the xor of `vars` equals `rhs`
+// `assigned` keeps the true lits of the vars that were substituted out of the row,
+// they are the reason of everything derived from the row
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    vars: BTreeSet<Var>,
+    rhs: bool,
+    assigned: BTreeSet<Lit>,
+    // the var that occurs in no other row, set by the last elimination
+    pivot: Option<Var>,
+}
+
+impl Row {
+    // add (xor) another row into this one, the vars and lits that occur in both cancel out
+    fn add(&mut self, other: &Row) {
+        self.vars = &self.vars ^ &other.vars;
+        self.rhs ^= other.rhs;
+        self.assigned = &self.assigned ^ &other.assigned;
+    }
+
+    fn reason(&self, implied: Option<Lit>) -> Clause {
+        let mut lits = implied.into_iter().collect::<Vec<_>>();
+        lits.extend(self.assigned.iter().map(|&lit| !lit));
+        Clause(lits)
+    }
+}
+
+/// What the matrix derives from the current assignment.
+///
+/// Every implication carries a clause that is implied by the xor constraints and explains it:
+/// the implied lit together with the negation of the assignments it depends on, or only the
+/// negated assignments in case of a conflict.
+#[derive(Debug, Clone)]
+pub enum XorImplication {
+    Unit(Lit, Clause),
+    Conflict(Clause),
+}
+
+/// A set of xor constraints kept in reduced row echelon form by Gauss-Jordan elimination.
+#[derive(Debug, Clone, Default)]
+pub struct XorMatrix {
+    rows: Vec<Row>,
+    // the vars whose unit was already returned by `eliminate`, until they are assigned
+    implied: BTreeSet<Var>,
+}
+
+impl XorMatrix {
+    pub fn new() -> XorMatrix {
+        XorMatrix::default()
+    }
+
+    /// Adds the constraint `lits[0] ^ lits[1] ^ ... == rhs`.
+    ///
+    /// Negative lits flip the right hand side and a var that occurs twice cancels out.
+    pub fn add_xor(&mut self, lits: &[Lit], rhs: bool) {
+        let mut vars = BTreeSet::new();
+        let mut rhs = rhs;
+        for &lit in lits {
+            rhs ^= lit.is_negative();
+            if !vars.insert(lit.var()) {
+                vars.remove(&lit.var());
+            }
+        }
+        self.rows.push(Row {
+            vars,
+            rhs,
+            assigned: BTreeSet::new(),
+            pivot: None,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn contains(&self, var: Var) -> bool {
+        self.rows.iter().any(|row| row.vars.contains(&var))
+    }
+
+    /// All the unassigned vars that still occur in some row.
+    pub fn vars(&self) -> BTreeSet<Var> {
+        self.rows
+            .iter()
+            .flat_map(|row| row.vars.iter().cloned())
+            .collect()
+    }
+
+    // based on lit is true
+    // substitute the var of lit in every row that contains it
+    pub fn assign(&mut self, lit: Lit) {
+        self.implied.remove(&lit.var());
+        for row in self.rows.iter_mut() {
+            if row.vars.remove(&lit.var()) {
+                row.rhs ^= lit.is_positive();
+                row.assigned.insert(lit);
+            }
+        }
+    }
+
+    /// Brings the matrix back to reduced row echelon form and collects the rows that became unit.
+    ///
+    /// Satisfied rows are dropped. When a row reduces to `0 == 1` only that conflict is returned.
+    /// A unit is returned once, a row that stays unit until its var is assigned is not returned
+    /// again.
+    pub fn eliminate(&mut self) -> Vec<XorImplication> {
+        let mut i = 0;
+        while i < self.rows.len() {
+            let pivot = match self.rows[i].vars.iter().next() {
+                Some(&var) => var,
+                None => {
+                    let row = self.rows.remove(i);
+                    if row.rhs {
+                        return vec![XorImplication::Conflict(row.reason(None))];
+                    }
+                    continue;
+                }
+            };
+            // the pivot of row i must not occur in any other row
+            self.rows[i].pivot = Some(pivot);
+            let pivot_row = self.rows[i].clone();
+            for (j, row) in self.rows.iter_mut().enumerate() {
+                if j != i && row.vars.contains(&pivot) {
+                    row.add(&pivot_row);
+                }
+            }
+            i += 1;
+        }
+
+        let mut units = vec![];
+        for row in self.rows.iter().filter(|row| row.vars.len() == 1) {
+            let var = *row.vars.iter().next().unwrap();
+            if self.implied.insert(var) {
+                let lit = var.lit(row.rhs);
+                units.push(XorImplication::Unit(lit, row.reason(Some(lit))));
+            }
+        }
+        units
+    }
+
+    /// An assignment of the remaining vars that satisfies every row.
+    ///
+    /// The matrix has to be eliminated without conflict, then the pivot of each row occurs in no
+    /// other row, so the free vars are set to false and every pivot to the right hand side of its
+    /// row.
+    pub fn model(&self) -> Vec<Lit> {
+        let mut lits = Vec::new();
+        for row in self.rows.iter() {
+            for &var in row.vars.iter() {
+                lits.push(var.lit(Some(var) == row.pivot && row.rhs));
+            }
+        }
+        lits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn lits(lits: &[isize]) -> Vec<Lit> {
+        lits.iter().map(|&lit| Lit::from_dimacs(lit)).collect()
+    }
+
+    #[test]
+    fn eliminate_to_unit() {
+        // x1 ^ x2 == 1, x2 ^ x3 == 0, x1 ^ x3 ^ x4 == 1  =>  x4 == 0
+        let mut matrix = XorMatrix::new();
+        matrix.add_xor(&lits(&[1, 2]), true);
+        matrix.add_xor(&lits(&[2, 3]), false);
+        matrix.add_xor(&lits(&[1, 3, 4]), true);
+        let implied = matrix.eliminate();
+        assert_eq!(implied.len(), 1);
+        match &implied[0] {
+            XorImplication::Unit(lit, reason) => {
+                assert_eq!(*lit, Lit::from_dimacs(-4));
+                assert_eq!(reason.inner(), &[Lit::from_dimacs(-4)]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        // the unit is pending until x4 is assigned
+        assert!(matrix.eliminate().is_empty());
+        matrix.assign(Lit::from_dimacs(-4));
+        assert!(matrix.eliminate().is_empty());
+    }
+
+    #[test]
+    fn unit_once_in_cnf() {
+        // x1 ^ x2 == 1 with x1 assigned implies -x2, the next propagation must not add it again
+        let clauses = crate::Clauses::from(&[vec![1, 3, 4], vec![-3, 4, 5]][..]);
+        let mut cnf = crate::CnfGraph::from(clauses);
+        cnf.add_xor(&lits(&[1, 2]), true);
+        cnf.propagation(Lit::from_dimacs(1)).unwrap();
+        let added = cnf.next_id;
+        cnf.add_xor(&lits(&[3, 5]), false);
+        cnf.propagation(Lit::from_dimacs(3)).unwrap();
+        // only the unit x5 of the new row is added
+        assert_eq!(cnf.next_id, added + 1);
+    }
+
+    #[test]
+    fn reason_of_unit() {
+        // x1 ^ x2 ^ x3 == 1, with x1 and -x2 assigned  =>  x3 == 0
+        let mut matrix = XorMatrix::new();
+        matrix.add_xor(&lits(&[1, 2, 3]), true);
+        matrix.assign(Lit::from_dimacs(1));
+        matrix.assign(Lit::from_dimacs(-2));
+        match &matrix.eliminate()[..] {
+            [XorImplication::Unit(lit, reason)] => {
+                assert_eq!(*lit, Lit::from_dimacs(-3));
+                let mut reason = reason.inner().to_vec();
+                reason.sort();
+                assert_eq!(reason, lits(&[-1, 2, -3]));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn conflict() {
+        // x1 ^ x2 == 1 and -x1 ^ x2 == 1 (i.e. x1 ^ x2 == 0)
+        let mut matrix = XorMatrix::new();
+        matrix.add_xor(&lits(&[1, 2]), true);
+        matrix.add_xor(&lits(&[-1, 2]), true);
+        match &matrix.eliminate()[..] {
+            [XorImplication::Conflict(reason)] => assert!(reason.inner().is_empty()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn model() {
+        let mut matrix = XorMatrix::new();
+        matrix.add_xor(&lits(&[1, 2, 3]), true);
+        matrix.add_xor(&lits(&[2, 4]), false);
+        matrix.add_xor(&lits(&[1, 4, 5]), false);
+        assert!(matrix.eliminate().is_empty());
+        let model = matrix.model();
+        let value = |var: isize| model.contains(&Lit::from_dimacs(var));
+        assert!(value(1) ^ value(2) ^ value(3));
+        assert!(!(value(2) ^ value(4)));
+        assert!(!(value(1) ^ value(4) ^ value(5)));
+    }
+}